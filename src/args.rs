@@ -0,0 +1,175 @@
+use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
+use weather_core::config::{ConfigLayer, ProviderKind};
+use weather_core::favorites::ExchangeFormat;
+use weather_core::units::{LengthUnit, SpeedUnit, TempUnit, UnitSystem};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Weather forecast app")]
+pub struct Args {
+    /// Print the forecast to stdout instead of opening the window
+    #[arg(long)]
+    pub cli: bool,
+
+    /// Location or coordinates (decimal, DMS, plus code or geo: link) for --cli, defaults to the configured location
+    #[arg(requires = "cli")]
+    pub query: Option<String>,
+
+    /// Output format for --cli
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, requires = "cli")]
+    pub format: OutputFormat,
+
+    /// Write the favorites to a file ("-" for stdout) and exit
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["cli", "import"])]
+    pub export: Option<PathBuf>,
+
+    /// Show what importing favorites from a file would add, and add them with --yes
+    #[arg(long, value_name = "PATH", conflicts_with = "cli")]
+    pub import: Option<PathBuf>,
+
+    /// Format for --export and --import: json, csv, geojson or gpx (default: from the file extension)
+    #[arg(long, value_name = "FORMAT")]
+    pub favorites_format: Option<ExchangeFormat>,
+
+    /// Apply the --import instead of only showing it
+    #[arg(long, requires = "import")]
+    pub yes: bool,
+
+    /// With --import, also take the names and groups of places that are already favorites
+    #[arg(long, requires = "import")]
+    pub update_existing: bool,
+
+    /// Config file to use instead of $XDG_CONFIG_HOME/rust-weather-app/config.toml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Weather provider: visual-crossing or open-meteo
+    #[arg(long)]
+    pub provider: Option<ProviderKind>,
+
+    /// Don't fall back to Open-Meteo when the main provider fails
+    #[arg(long)]
+    pub no_fallback: bool,
+
+    /// Visual Crossing API key
+    #[arg(long, value_name = "KEY")]
+    pub api_key: Option<String>,
+
+    /// Location shown at startup
+    #[arg(long, value_name = "LOCATION")]
+    pub location: Option<String>,
+
+    /// Unit system: metric, us, uk or si
+    #[arg(long)]
+    pub units: Option<UnitSystem>,
+
+    /// Temperature unit, overrides the unit system: celsius, fahrenheit or kelvin
+    #[arg(long, value_name = "UNIT")]
+    pub temperature_unit: Option<TempUnit>,
+
+    /// Wind speed unit, overrides the unit system: kmh, mph, ms or knots
+    #[arg(long, value_name = "UNIT")]
+    pub wind_unit: Option<SpeedUnit>,
+
+    /// Precipitation unit, overrides the unit system: mm, cm or inch
+    #[arg(long, value_name = "UNIT")]
+    pub precip_unit: Option<LengthUnit>,
+
+    /// Snow unit, overrides the unit system: mm, cm or inch
+    #[arg(long, value_name = "UNIT")]
+    pub snow_unit: Option<LengthUnit>,
+
+    /// Language for condition descriptions and place names
+    #[arg(long)]
+    pub lang: Option<String>,
+
+    /// How many times a request that failed for a passing reason is retried
+    #[arg(long, value_name = "N")]
+    pub max_retries: Option<u32>,
+
+    /// Seconds a request may take with all its retries
+    #[arg(long, value_name = "SECS")]
+    pub retry_deadline: Option<u64>,
+
+    /// Minutes a cached forecast is considered fresh
+    #[arg(long, value_name = "MINUTES")]
+    pub cache_ttl: Option<u64>,
+
+    /// Only show cached forecasts, never go to the network
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Minutes between notification rule checks, 0 turns them off
+    #[arg(long, value_name = "MINUTES")]
+    pub rules_check: Option<u64>,
+
+    /// Minutes between background refreshes of the forecast and favorites, 0 turns them off
+    #[arg(long, value_name = "MINUTES")]
+    pub refresh: Option<u64>,
+
+    /// GeoNames city file for offline search and naming (default: cities15000.txt in the data dir)
+    #[arg(long, value_name = "PATH")]
+    pub cities_file: Option<PathBuf>,
+
+    /// How many recent searches are remembered, 0 turns the history off
+    #[arg(long, value_name = "N")]
+    pub history_size: Option<usize>,
+
+    /// Directory for favorites, search history and the city list (default: $XDG_DATA_HOME/rust-weather-app)
+    #[arg(long, value_name = "PATH")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Seconds to wait for a connection to the weather service
+    #[arg(long, value_name = "SECS")]
+    pub connect_timeout: Option<u64>,
+
+    /// Seconds to wait for the weather service to send more data
+    #[arg(long, value_name = "SECS")]
+    pub read_timeout: Option<u64>,
+
+    /// Proxy for every request: http://, https://, socks5:// or socks5h://host:port
+    #[arg(long, value_name = "URL")]
+    pub proxy: Option<String>,
+
+    /// PEM file with extra root certificates to trust
+    #[arg(long, value_name = "PATH")]
+    pub ca_file: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl Args {
+    pub fn config_layer(&self) -> ConfigLayer {
+        ConfigLayer {
+            provider: self.provider,
+            fallback: self.no_fallback.then_some(false),
+            api_key: self.api_key.clone(),
+            open_meteo_url: None,
+            default_location: self.location.clone(),
+            units: self.units,
+            temperature_unit: self.temperature_unit,
+            wind_unit: self.wind_unit,
+            precip_unit: self.precip_unit,
+            snow_unit: self.snow_unit,
+            lang: self.lang.clone(),
+            max_retries: self.max_retries,
+            retry_deadline_secs: self.retry_deadline,
+            cache_ttl_minutes: self.cache_ttl,
+            offline: self.offline.then_some(true),
+            rules_check_minutes: self.rules_check,
+            refresh_minutes: self.refresh,
+            cities_file: self.cities_file.clone(),
+            history_size: self.history_size,
+            data_dir: self.data_dir.clone(),
+            connect_timeout_secs: self.connect_timeout,
+            read_timeout_secs: self.read_timeout,
+            proxy: self.proxy.clone(),
+            ca_file: self.ca_file.clone(),
+        }
+    }
+}
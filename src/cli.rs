@@ -0,0 +1,463 @@
+use crate::args::{Args, OutputFormat};
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use weather_core::astro::moon_phase_name;
+use weather_core::config::Config;
+use weather_core::coords::format_coords;
+use weather_core::favorites::{self, FavoritesStore};
+use weather_core::format::{
+    compass_point, csv_field, date_to_day, hours_minutes, short_time, signed_minutes_seconds,
+};
+use weather_core::geocoding::CityIndex;
+use weather_core::providers::WeatherProvider;
+use weather_core::query::Query;
+use weather_core::units::{Units, round1};
+use weather_core::weather_structs::{
+    ForecastDay, ForecastHour, ForecastResponse, WeatherAlert, active_alerts,
+};
+
+/// `--cli`: fetch once, print, exit. No window is created.
+pub async fn run(
+    args: &Args,
+    config: &Config,
+    provider: &dyn WeatherProvider,
+    cities: Option<&CityIndex>,
+) -> Result<(), anyhow::Error> {
+    let query = args
+        .query
+        .clone()
+        .unwrap_or_else(|| config.default_location.clone());
+
+    let query = Query::from_text(query, cities).map_err(|message| anyhow::anyhow!(message))?;
+    let forecast = query
+        .fetch(provider, cities)
+        .await
+        .map_err(|e| anyhow::anyhow!("{} ({e})", e.user_message()))?;
+    if forecast.days.is_empty() {
+        return Err(anyhow::anyhow!("API returned no forecast days"));
+    }
+
+    let report = Report::new(&forecast, config.display_units());
+    let output = match args.format {
+        OutputFormat::Table => report.to_table(),
+        OutputFormat::Json => serde_json::to_string_pretty(&report)? + "\n",
+        OutputFormat::Csv => report.to_csv(),
+    };
+    print!("{output}");
+
+    Ok(())
+}
+
+/// `--export` / `--import`: move favorites in and out of other apps' files. An import
+/// only prints what it would do until it's run again with `--yes`.
+pub fn transfer(args: &Args, config: &Config) -> Result<(), anyhow::Error> {
+    let mut store = FavoritesStore::from_config(config)?;
+
+    if let Some(path) = &args.export {
+        if path.as_os_str() == "-" {
+            let format = args
+                .favorites_format
+                .unwrap_or(favorites::ExchangeFormat::Json);
+            print!("{}", favorites::export(store.favorites(), format));
+        } else {
+            favorites::export_file(path, store.favorites(), args.favorites_format)?;
+            eprintln!(
+                "Exported {} favorites to {}",
+                store.favorites().len(),
+                path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let Some(path) = &args.import else {
+        return Ok(());
+    };
+    let incoming = favorites::import_file(path, args.favorites_format)?;
+    let preview = store.preview_import(incoming);
+
+    for favorite in &preview.new {
+        println!(
+            "+ {} ({})",
+            favorite.name(),
+            format_coords(favorite.lat, favorite.lon)
+        );
+    }
+    for (favorite, index) in &preview.duplicates {
+        println!(
+            "= {} is already saved as {}",
+            favorite.name(),
+            store.favorites()[*index].name()
+        );
+    }
+    println!(
+        "{} new, {} already saved",
+        preview.new.len(),
+        preview.duplicates.len()
+    );
+
+    if !args.yes {
+        println!("Nothing was changed, run again with --yes to import");
+        return Ok(());
+    }
+    let changed = store.import(preview, args.update_existing)?;
+    println!("Imported, {changed} favorites added or updated");
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Report {
+    location: String,
+    latitude: f64,
+    longitude: f64,
+    fetched_at: String,
+    stale: bool,
+    units: UnitLabels,
+    /// Active and upcoming, most severe first
+    alerts: Vec<AlertReport>,
+    days: Vec<DayReport>,
+    /// Hour by hour for today
+    hours: Vec<HourReport>,
+}
+
+#[derive(Serialize)]
+struct UnitLabels {
+    temperature: &'static str,
+    wind_speed: &'static str,
+    precip: &'static str,
+    snow: &'static str,
+}
+
+#[derive(Serialize)]
+struct AlertReport {
+    event: String,
+    severity: &'static str,
+    onset: Option<String>,
+    ends: Option<String>,
+    area: Option<String>,
+    description: String,
+}
+
+#[derive(Serialize)]
+struct DayReport {
+    date: String,
+    weekday: String,
+    conditions: String,
+    icon: String,
+    temp: f32,
+    temp_min: f32,
+    temp_max: f32,
+    feels_like: f32,
+    humidity: f32,
+    precip: f32,
+    precip_prob: f32,
+    snow: f32,
+    snow_depth: f32,
+    wind_speed: f32,
+    wind_dir: f32,
+    aqi_eur: Option<f32>,
+    /// 0-100 risk of severe storms
+    severe_risk: Option<f32>,
+    sunrise: Option<String>,
+    sunset: Option<String>,
+    /// Seconds
+    daylight: Option<i64>,
+    /// Seconds versus the day before
+    daylight_change: Option<i64>,
+    moon_phase: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct HourReport {
+    time: String,
+    conditions: String,
+    icon: String,
+    temp: f32,
+    feels_like: f32,
+    humidity: f32,
+    precip: f32,
+    precip_prob: f32,
+    wind_speed: f32,
+    wind_dir: f32,
+    cloud_cover: f32,
+}
+
+impl DayReport {
+    fn new(day: &ForecastDay, units: Units) -> Self {
+        DayReport {
+            date: day.datetime.clone(),
+            weekday: date_to_day(day.datetime.clone()),
+            conditions: day.conditions.clone(),
+            icon: day.icon.clone(),
+            temp: round1(units.temp_value(day.temp)),
+            temp_min: round1(units.temp_value(day.temp_min)),
+            temp_max: round1(units.temp_value(day.temp_max)),
+            feels_like: round1(units.temp_value(day.feels_like)),
+            humidity: day.humidity,
+            precip: round2(units.precip_value(day.precip)),
+            precip_prob: day.precip_prob,
+            snow: round2(units.snow_value(day.snow)),
+            snow_depth: round2(units.snow_value(day.snow_depth)),
+            wind_speed: round1(units.speed_value(day.wind_speed)),
+            wind_dir: day.wind_dir,
+            aqi_eur: day.aqieur,
+            severe_risk: day.severe_risk,
+            sunrise: day.sunrise.clone(),
+            sunset: day.sunset.clone(),
+            daylight: day.daylight,
+            daylight_change: day.daylight_change,
+            moon_phase: day.moon_phase,
+        }
+    }
+}
+
+impl AlertReport {
+    fn new(alert: WeatherAlert) -> Self {
+        let local = |time: DateTime<Utc>| time.with_timezone(&Local).to_rfc3339();
+        AlertReport {
+            severity: alert.severity.label(),
+            onset: alert.onset.map(local),
+            ends: alert.ends.map(local),
+            event: alert.event,
+            area: alert.area,
+            description: alert.description,
+        }
+    }
+}
+
+impl HourReport {
+    fn new(hour: &ForecastHour, units: Units) -> Self {
+        HourReport {
+            time: hour.datetime.clone(),
+            conditions: hour.conditions.clone(),
+            icon: hour.icon.clone(),
+            temp: round1(units.temp_value(hour.temp)),
+            feels_like: round1(units.temp_value(hour.feels_like)),
+            humidity: hour.humidity,
+            precip: round2(units.precip_value(hour.precip)),
+            precip_prob: hour.precip_prob,
+            wind_speed: round1(units.speed_value(hour.wind_speed)),
+            wind_dir: hour.wind_dir,
+            cloud_cover: hour.cloud_cover,
+        }
+    }
+}
+
+impl Report {
+    fn new(forecast: &ForecastResponse, units: Units) -> Self {
+        let today = &forecast.days[0];
+
+        Report {
+            location: forecast.resolved_address.clone(),
+            latitude: forecast.latitude,
+            longitude: forecast.longitude,
+            fetched_at: forecast.fetched_at.with_timezone(&Local).to_rfc3339(),
+            stale: forecast.stale,
+            units: UnitLabels {
+                temperature: units.temp_symbol(),
+                wind_speed: units.speed_symbol(),
+                precip: units.precip_symbol(),
+                snow: units.snow_symbol(),
+            },
+            alerts: active_alerts(&forecast.alerts, Utc::now())
+                .into_iter()
+                .map(AlertReport::new)
+                .collect(),
+            days: forecast
+                .days
+                .iter()
+                .map(|day| DayReport::new(day, units))
+                .collect(),
+            hours: today
+                .hours
+                .iter()
+                .map(|hour| HourReport::new(hour, units))
+                .collect(),
+        }
+    }
+
+    fn to_table(&self) -> String {
+        let u = &self.units;
+        let today = &self.days[0];
+        let mut out = String::new();
+
+        out += &format!(
+            "{} ({:.4}, {:.4})\n",
+            self.location, self.latitude, self.longitude
+        );
+        if self.stale {
+            out += &format!("Offline, last updated {}\n", self.fetched_at);
+        }
+        out += "\n";
+
+        for alert in &self.alerts {
+            out += &format!("ALERT: {} ({})", alert.event, alert.severity);
+            if let Some(ends) = &alert.ends {
+                out += &format!(", until {ends}");
+            }
+            if let Some(area) = &alert.area {
+                out += &format!(", {area}");
+            }
+            out += "\n";
+        }
+        if !self.alerts.is_empty() {
+            out += "\n";
+        }
+
+        out += &format!(
+            "Today: {}, {}{} (feels like {}{})\n",
+            today.conditions, today.temp, u.temperature, today.feels_like, u.temperature
+        );
+        out += &format!(
+            "  Low {}{}, high {}{}\n",
+            today.temp_min, u.temperature, today.temp_max, u.temperature
+        );
+        out += &format!(
+            "  Precipitation {} {} ({}%), humidity {}%{}\n",
+            today.precip,
+            u.precip,
+            today.precip_prob,
+            today.humidity,
+            today
+                .severe_risk
+                .map_or(String::new(), |risk| format!(", storm risk {risk}%"))
+        );
+        out += &format!(
+            "  Snow {} {}, depth {} {}\n",
+            today.snow, u.snow, today.snow_depth, u.snow
+        );
+        out += &format!(
+            "  Wind {} {} from {}°, AQI (EUR) {}\n",
+            today.wind_speed,
+            u.wind_speed,
+            today.wind_dir,
+            today
+                .aqi_eur
+                .map_or("N/A".to_string(), |aqi| aqi.to_string())
+        );
+        out += &format!(
+            "  Sunrise {}, sunset {}, daylight {} ({} vs yesterday)\n",
+            today
+                .sunrise
+                .as_deref()
+                .map_or("None".to_string(), short_time),
+            today
+                .sunset
+                .as_deref()
+                .map_or("None".to_string(), short_time),
+            today.daylight.map_or("N/A".to_string(), hours_minutes),
+            today
+                .daylight_change
+                .map_or("N/A".to_string(), signed_minutes_seconds)
+        );
+        out += &format!(
+            "  Moon: {}\n\n",
+            today.moon_phase.map_or("N/A", moon_phase_name)
+        );
+
+        out += &format!(
+            "{:<10} {:<10} {:>8} {:>8} {:>8}  {}\n",
+            "Day", "Date", "Temp", "Low", "High", "Conditions"
+        );
+        for day in &self.days {
+            out += &format!(
+                "{:<10} {:<10} {:>8} {:>8} {:>8}  {}\n",
+                day.weekday,
+                day.date,
+                format!("{}{}", day.temp, u.temperature),
+                format!("{}{}", day.temp_min, u.temperature),
+                format!("{}{}", day.temp_max, u.temperature),
+                day.conditions
+            );
+        }
+        out += "\n";
+
+        out += &format!(
+            "{:<5} {:>8} {:>8} {:>12} {:>5} {:>14}  {}\n",
+            "Hour", "Temp", "Feels", "Precip", "Prob", "Wind", "Conditions"
+        );
+        for hour in &self.hours {
+            out += &format!(
+                "{:<5} {:>8} {:>8} {:>12} {:>5} {:>14}  {}\n",
+                hour.time.get(..5).unwrap_or(&hour.time),
+                format!("{}{}", hour.temp, u.temperature),
+                format!("{}{}", hour.feels_like, u.temperature),
+                format!("{} {}", hour.precip, u.precip),
+                format!("{}%", hour.precip_prob),
+                format!(
+                    "{} {} {}",
+                    hour.wind_speed,
+                    u.wind_speed,
+                    compass_point(hour.wind_dir)
+                ),
+                hour.conditions
+            );
+        }
+
+        out
+    }
+
+    /// One table with a `kind` column, so both days and hours survive a plain CSV reader.
+    /// Hour rows leave the daily-only columns empty, day rows leave `cloud_cover` empty.
+    /// `daylight` and `daylight_change` are in seconds.
+    fn to_csv(&self) -> String {
+        let mut out = String::from(
+            "kind,date,time,temp,temp_min,temp_max,feels_like,humidity,precip,precip_prob,snow,snow_depth,wind_speed,wind_dir,aqi_eur,severe_risk,cloud_cover,sunrise,sunset,daylight,daylight_change,moon_phase,conditions,icon\n",
+        );
+
+        for day in &self.days {
+            out += &format!(
+                "day,{},,{},{},{},{},{},{},{},{},{},{},{},{},{},,{},{},{},{},{},{},{}\n",
+                day.date,
+                day.temp,
+                day.temp_min,
+                day.temp_max,
+                day.feels_like,
+                day.humidity,
+                day.precip,
+                day.precip_prob,
+                day.snow,
+                day.snow_depth,
+                day.wind_speed,
+                day.wind_dir,
+                day.aqi_eur.map_or(String::new(), |aqi| aqi.to_string()),
+                day.severe_risk
+                    .map_or(String::new(), |risk| risk.to_string()),
+                day.sunrise.as_deref().unwrap_or_default(),
+                day.sunset.as_deref().unwrap_or_default(),
+                day.daylight.map_or(String::new(), |secs| secs.to_string()),
+                day.daylight_change
+                    .map_or(String::new(), |secs| secs.to_string()),
+                day.moon_phase
+                    .map_or(String::new(), |phase| phase.to_string()),
+                csv_field(&day.conditions),
+                csv_field(&day.icon)
+            );
+        }
+
+        let today = &self.days[0].date;
+        for hour in &self.hours {
+            out += &format!(
+                "hour,{today},{},{},,,{},{},{},{},,,{},{},,,{},,,,,,{},{}\n",
+                hour.time,
+                hour.temp,
+                hour.feels_like,
+                hour.humidity,
+                hour.precip,
+                hour.precip_prob,
+                hour.wind_speed,
+                hour.wind_dir,
+                hour.cloud_cover,
+                csv_field(&hour.conditions),
+                csv_field(&hour.icon)
+            );
+        }
+
+        out
+    }
+}
+
+// lengths can be in inches, where the second decimal still matters
+fn round2(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0 + 0.0
+}
@@ -1,386 +1,923 @@
-use chrono::Datelike;
-mod weather_structs;
-
-use crate::weather_structs::{
-    FavoriteLocation, FavoriteWeatherResponse, ForecastDay, ForecastHour, ForecastResponse,
-};
-use chrono::NaiveDate;
+mod args;
+mod cli;
+mod notifications;
+
+use crate::args::Args;
+use crate::notifications::DesktopNotifier;
+use chrono::{DateTime, Local, Utc};
+use clap::Parser;
 use slint::SharedString;
-use std::fs;
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Notify};
+use weather_core::astro;
+use weather_core::config::Config;
+use weather_core::coords::format_coords;
+use weather_core::error::WeatherError;
+use weather_core::favorites::{self, ExchangeFormat, FavoritesStore, ImportPreview};
+use weather_core::format::{
+    capitalize_first, compass_point, date_to_day, hours_minutes, short_time,
+    signed_minutes_seconds, time_ago,
+};
+use weather_core::geocoding::{self, CityIndex, Geocoder, Place};
+use weather_core::history::SearchHistory;
+use weather_core::http;
+use weather_core::providers::{self, WeatherProvider};
+use weather_core::query::Query;
+use weather_core::refresh::RefreshSchedule;
+use weather_core::rules::{
+    Comparison, DaySelector, HourWindow, Metric, Rule, RuleEngine, RuleMatch, read_rules_from_file,
+    write_rules_to_file,
+};
+use weather_core::units::{UnitSystem, Units, round1};
+use weather_core::weather_structs::{
+    CurrentConditions, FavoriteLocation, ForecastDay, ForecastResponse, WeatherAlert, active_alerts,
+};
 
-const API_KEY: &str = "8CYFEA2MNNBQD7YG5C6YDKB5K";
+fn update_hours(ui_weak: slint::Weak<MainWindow>, day: &ForecastDay, units: Units) {
+    let forecast_hours: Vec<Hour> = day
+        .hours
+        .iter()
+        .map(|hour| Hour {
+            index: SharedString::from(match hour.datetime.split(":").next() {
+                Some(result) => result.to_string(),
+                None => "N/A".to_string(),
+            }),
+            temp: SharedString::from(units.temp(hour.temp)),
+            icon: SharedString::from(hour.icon.clone()),
+            conditions: SharedString::from(hour.conditions.clone()),
+            feels_like: SharedString::from(units.temp(hour.feels_like)),
+            humidity: SharedString::from(format!("{}%", round1(hour.humidity))),
+            precip: SharedString::from(units.precip(hour.precip)),
+            precip_prob: SharedString::from(format!("{}%", round1(hour.precip_prob))),
+            wind: SharedString::from(format!(
+                "{} {}",
+                units.speed(hour.wind_speed),
+                compass_point(hour.wind_dir)
+            )),
+            cloud_cover: SharedString::from(format!("{}%", round1(hour.cloud_cover))),
+        })
+        .collect();
 
-// AM PUS UNDERLINE PT CA ESTE UN STRUCT PE CARE NU IL CONSTRUIESC EFECTIV, IL FOLOSESC PENTRU A TRIMITE DATELE LA SLINT
-#[derive(Clone)]
-struct _DayData {
-    datetime: SharedString,
-    temp: f32,
-    temp_max: f32,
-    temp_min: f32,
-}
-
-impl From<&ForecastDay> for _DayData {
-    fn from(day: &ForecastDay) -> Self {
-        _DayData {
-            datetime: SharedString::from(day.datetime.clone()),
-            temp: day.temp,
-            temp_max: day.temp_max,
-            temp_min: day.temp_min,
-        }
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let hours_model = std::rc::Rc::new(slint::VecModel::from(forecast_hours));
+        ui.set_hours(hours_model.into());
+    }) {
+        eprintln!("Failed to update hours in UI: {:?}", e);
     }
 }
 
-#[derive(Clone)]
-struct _HourData {
-    temp: f32,
-    index: String,
+async fn display_weather_info(
+    provider: &dyn WeatherProvider,
+    cities: Option<&CityIndex>,
+    query: &Query,
+    ui_weak: slint::Weak<MainWindow>,
+    units: Units,
+) -> Result<ForecastResponse, WeatherError> {
+    let value = query.fetch(provider, cities).await?;
+    show_forecast(ui_weak, &value, units)?;
+    Ok(value)
 }
 
-impl From<&ForecastHour> for _HourData {
-    fn from(hour: &ForecastHour) -> Self {
-        _HourData {
-            temp: hour.temp,
-            index: match hour.datetime.split(":").next() {
-                Some(result) => result.to_string(),
-                None => "N/A".to_string(),
-            },
-        }
+fn show_forecast(
+    ui_weak: slint::Weak<MainWindow>,
+    forecast: &ForecastResponse,
+    units: Units,
+) -> Result<(), WeatherError> {
+    let today = forecast
+        .days
+        .first()
+        .ok_or_else(|| WeatherError::Parse("API returned no forecast days".to_string()))?;
+
+    let address = forecast.resolved_address.clone();
+
+    match ui_weak.upgrade_in_event_loop(move |ui| {
+        ui.set_location_name(SharedString::from(address));
+    }) {
+        Ok(_) => println!("Updated UI successfully!"),
+        Err(e) => eprintln!("Failed to update the UI: {e}"),
     }
+
+    update_last_updated(ui_weak.clone(), forecast);
+    update_alerts(ui_weak.clone(), forecast);
+    update_days_list(ui_weak.clone(), forecast, units);
+    update_day_display(ui_weak.clone(), today, units);
+
+    Ok(())
 }
 
-struct _FavoriteLocation {
-    icon: String,
-    name: String,
-    temp: f32,
-    temp_min: f32,
-    temp_max: f32,
+fn update_last_updated(ui_weak: slint::Weak<MainWindow>, forecast: &ForecastResponse) {
+    let ago = time_ago(forecast.fetched_at, Utc::now());
+    let label = if forecast.stale {
+        format!("Offline, last updated {ago}")
+    } else {
+        format!("Updated {ago}")
+    };
+    let stale = forecast.stale;
+
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        ui.set_last_updated(SharedString::from(label));
+        ui.set_showing_stale(stale);
+    }) {
+        eprintln!("Failed to update last updated label: {e}")
+    }
 }
 
-impl From<&FavoriteWeatherResponse> for _FavoriteLocation {
-    fn from(resp: &FavoriteWeatherResponse) -> Self {
-        _FavoriteLocation {
-            icon: resp.days[0].icon.clone(),
-            name: resp._resolved_address.clone(),
-            temp: resp.days[0].temp,
-            temp_max: resp.days[0].tempmax,
-            temp_min: resp.days[0].tempmin,
-        }
+fn update_alerts(ui_weak: slint::Weak<MainWindow>, forecast: &ForecastResponse) {
+    let now = Utc::now();
+    let alerts: Vec<Alert> = active_alerts(&forecast.alerts, now)
+        .into_iter()
+        .map(|alert| Alert {
+            time_range: SharedString::from(alert_time_range(&alert, now)),
+            event: SharedString::from(alert.event),
+            severity: SharedString::from(alert.severity.label()),
+            area: SharedString::from(alert.area.unwrap_or_default()),
+            description: SharedString::from(alert.description),
+        })
+        .collect();
+
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let alerts_model = std::rc::Rc::new(slint::VecModel::from(alerts));
+        ui.set_alerts(alerts_model.into());
+    }) {
+        eprintln!("Failed to update alerts: {e}")
     }
 }
 
-fn capitalize_first(string: String) -> String {
-    let mut result = String::new();
-    let mut first = true;
-    for char in string.chars() {
-        if first {
-            first = false;
-            result.push(char.to_ascii_uppercase());
-        } else {
-            result.push(char);
-            if char == ' ' {
-                first = true;
-            }
+fn alert_time_range(alert: &WeatherAlert, now: DateTime<Utc>) -> String {
+    let local = |time: DateTime<Utc>| time.with_timezone(&Local).format("%a %H:%M").to_string();
+    match (alert.onset, alert.ends) {
+        (Some(onset), Some(ends)) if onset > now => {
+            format!("From {} until {}", local(onset), local(ends))
         }
+        (Some(onset), None) if onset > now => format!("From {}", local(onset)),
+        (_, Some(ends)) => format!("Until {}", local(ends)),
+        (_, None) => "Until further notice".to_string(),
     }
-
-    result
 }
 
-fn date_to_day(date: String) -> String {
-    let format = "%Y-%m-%d";
+/// Fetches the shown location again, keeping the selected day. Returns false when the
+/// fetch failed or only came back from the stale cache.
+async fn refresh_displayed(
+    provider: &dyn WeatherProvider,
+    cities: Option<&CityIndex>,
+    last_query: &Mutex<Option<Query>>,
+    last_forecast: &Mutex<Option<ForecastResponse>>,
+    units: &Mutex<Units>,
+    ui_weak: slint::Weak<MainWindow>,
+) -> bool {
+    let Some(query) = last_query.lock().await.clone() else {
+        return true;
+    };
 
-    let naive_date = match NaiveDate::parse_from_str(date.as_str(), format) {
-        Ok(date) => date,
+    let forecast = match query.fetch(provider, cities).await {
+        Ok(forecast) => forecast,
         Err(e) => {
-            eprintln!("Error parsing string to date: {e}");
-            return "Invalid Date".to_string();
+            eprintln!("Background refresh of {query} failed: {e}");
+            return false;
         }
     };
+    let fresh = !forecast.stale;
 
-    let weekday = naive_date.weekday();
+    let mut last = last_forecast.lock().await;
+    // the user searched for something else while this was loading
+    if last_query.lock().await.as_ref() != Some(&query) {
+        return true;
+    }
 
-    weekday.to_string()
-}
-
-fn update_hours(ui_weak: slint::Weak<MainWindow>, day: &ForecastDay) {
-    let forecast_hours: Vec<Hour> = day
-        .hours
-        .iter()
-        .map(|hour| Hour {
-            index: SharedString::from(match hour.datetime.split(":").next() {
-                Some(result) => result.to_string(),
-                None => "N/A".to_string(),
-            }),
-            temp: hour.temp,
-        })
-        .collect();
+    let units = *units.lock().await;
+    update_last_updated(ui_weak.clone(), &forecast);
+    update_alerts(ui_weak.clone(), &forecast);
+    update_days_list(ui_weak.clone(), &forecast, units);
 
+    let forecast_for_ui = forecast.clone();
     if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
-        let hours_model = std::rc::Rc::new(slint::VecModel::from(forecast_hours));
-        ui.set_hours(hours_model.into());
+        let selected = ui.get_selected_day_index() as usize;
+        if let Some(day) = forecast_for_ui.days.get(selected) {
+            update_day_display(ui.as_weak(), day, units);
+        }
     }) {
-        eprintln!("Failed to update hours in UI: {:?}", e);
+        eprintln!("Failed to show refreshed forecast: {e}");
     }
+
+    *last = Some(forecast);
+    fresh
 }
 
-async fn get_city_weather_info(location: String) -> Result<ForecastResponse, anyhow::Error> {
-    let url = format!(
-        "https://weather.visualcrossing.com/VisualCrossingWebServices/rest/services/timeline/{location}/next7days?unitGroup=metric&elements=add%3Aaqieur%2Cadd%3Atzoffset%2Cremove%3AdatetimeEpoch%2Cremove%3Adew%2Cremove%3Afeelslikemax%2Cremove%3Afeelslikemin%2Cremove%3Aprecipcover%2Cremove%3Apreciptype%2Cremove%3Apressure%2Cremove%3Asevererisk%2Cremove%3Asolarenergy%2Cremove%3Asolarradiation%2Cremove%3Astations%2Cremove%3Auvindex%2Cremove%3Awindgust&key={API_KEY}&lang=en&options=minuteinterval_10&contentType=json"
-    );
-    let response_json = reqwest::get(url).await?.text().await?;
-    let response: ForecastResponse = serde_json::from_str(&response_json)?;
+/// What the favorites panel last got for a favorite, an error when it couldn't be loaded
+type FavoriteWeather = (Result<CurrentConditions, WeatherError>, FavoriteLocation);
+
+/// Current conditions for every favorite, in saved order, failed ones included, and the
+/// first error if any failed
+async fn fetch_favorites_weather(
+    provider: Arc<dyn WeatherProvider>,
+    favorites: Vec<FavoriteLocation>,
+) -> (Vec<FavoriteWeather>, Option<WeatherError>) {
+    let points: Vec<(f64, f64)> = favorites.iter().map(|fav| (fav.lat, fav.lon)).collect();
+    let results = provider.current_many(&points).await;
+
+    let mut first_error = None;
+    for (result, fav_location) in results.iter().zip(&favorites) {
+        if let Err(e) = result {
+            eprintln!(
+                "Error getting location ({}, {}): {}",
+                fav_location.lat, fav_location.lon, e
+            );
+            first_error.get_or_insert(e.clone());
+        }
+    }
 
-    Ok(response)
+    (results.into_iter().zip(favorites).collect(), first_error)
 }
 
-async fn get_fav_city_info_by_coords(
-    lat: f64,
-    lon: f64,
-) -> Result<FavoriteWeatherResponse, anyhow::Error> {
-    let url = format!(
-        "https://weather.visualcrossing.com/VisualCrossingWebServices/rest/services/timeline/{},{}/today?unitGroup=metric&elements=temp,tempmax,tempmin,icon&key={API_KEY}&contentType=json&include=current",
-        lat, lon
-    );
-    let response_json = reqwest::get(url).await?.text().await?;
-    let response: FavoriteWeatherResponse = serde_json::from_str(&response_json)?;
-    Ok(response)
-}
-
-async fn get_fav_city_info_by_coords_with_retry(
-    lat: f64,
-    lon: f64,
-    max_retries: u32,
-) -> Result<FavoriteWeatherResponse, anyhow::Error> {
-    let mut retries = 0;
-    let mut delay = Duration::from_millis(100);
-
-    loop {
-        match get_fav_city_info_by_coords(lat, lon).await {
-            Ok(data) => return Ok(data),
-            Err(e) => {
-                if retries >= max_retries {
-                    return Err(e);
-                }
-                eprintln!("Retry {} for {},{}: {}", retries + 1, lat, lon, e);
-                tokio::time::sleep(delay).await;
-                retries += 1;
-                delay *= 2;
-            }
-        }
+// hidden or minimized windows don't need fresh data
+async fn window_visible(ui_weak: &slint::Weak<MainWindow>) -> bool {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    if ui_weak
+        .upgrade_in_event_loop(move |ui| {
+            let window = ui.window();
+            let _ = tx.send(window.is_visible() && !window.is_minimized());
+        })
+        .is_err()
+    {
+        return false;
     }
+    rx.await.unwrap_or(false)
 }
 
-fn write_favorites_to_file(favorites: Vec<FavoriteLocation>) {
-    let path = Path::new("./favorites.json");
-    match serde_json::to_string_pretty(&favorites) {
-        Ok(json_output) => {
-            match OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(path)
-            {
-                Ok(mut file) => match file.write_all(json_output.as_bytes()) {
-                    Ok(_) => {
-                        println!("Favorites saved successfully");
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to write to favorites.json: {}", e);
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Error opening favorites.json: {}", e);
+/// Keeps the shown forecast, the favorites and the "updated N min ago" label current.
+/// Wakes up every half minute; see `RefreshSchedule` for when it actually fetches.
+#[allow(clippy::too_many_arguments)]
+fn spawn_refresh_scheduler(
+    provider: Arc<dyn WeatherProvider>,
+    cities: Option<Arc<CityIndex>>,
+    interval: Duration,
+    last_query: Arc<Mutex<Option<Query>>>,
+    last_forecast: Arc<Mutex<Option<ForecastResponse>>>,
+    last_fav: Arc<Mutex<FavoritesStore>>,
+    last_fav_weather: Arc<Mutex<Vec<FavoriteWeather>>>,
+    units: Arc<Mutex<Units>>,
+    ui_weak: slint::Weak<MainWindow>,
+) {
+    const TICK: Duration = Duration::from_secs(30);
+
+    tokio::spawn(async move {
+        let mut schedule = RefreshSchedule::new(interval);
+        let mut ticker = tokio::time::interval(TICK);
+
+        loop {
+            ticker.tick().await;
+
+            let showing_stale = match last_forecast.lock().await.as_ref() {
+                Some(forecast) => {
+                    update_last_updated(ui_weak.clone(), forecast);
+                    forecast.stale
                 }
+                None => false,
+            };
+
+            if !schedule.is_due(Instant::now(), showing_stale) || !window_visible(&ui_weak).await {
+                continue;
             }
+
+            let mut succeeded = refresh_displayed(
+                provider.as_ref(),
+                cities.as_deref(),
+                &last_query,
+                &last_forecast,
+                &units,
+                ui_weak.clone(),
+            )
+            .await;
+
+            let favorites = last_fav.lock().await.favorites().to_vec();
+            if !favorites.is_empty() {
+                let (results, error) = fetch_favorites_weather(provider.clone(), favorites).await;
+                succeeded &= error.is_none();
+
+                *last_fav_weather.lock().await = results;
+                refresh_favorites_panel(&last_fav, &last_fav_weather, &units, ui_weak.clone())
+                    .await;
+            }
+
+            schedule.record(Instant::now(), succeeded);
         }
-        Err(e) => {
-            eprintln!("Error serializing favorites.json: {}", e);
-        }
+    });
+}
+
+fn update_days_list(ui_weak: slint::Weak<MainWindow>, forecast: &ForecastResponse, units: Units) {
+    let forecast_days: Vec<Day> = forecast
+        .days
+        .iter()
+        .map(|day| Day {
+            datetime: SharedString::from(date_to_day(day.datetime.clone())),
+            temp: SharedString::from(units.temp(day.temp)),
+            temp_max: SharedString::from(units.temp(day.temp_max)),
+            temp_min: SharedString::from(units.temp(day.temp_min)),
+        })
+        .collect();
+
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let days_model = std::rc::Rc::new(slint::VecModel::from(forecast_days));
+        ui.set_days(days_model.into());
+    }) {
+        eprintln!("Failed to update days list: {e}")
     }
 }
 
-fn read_favorites_from_file() -> Vec<FavoriteLocation> {
-    let path = Path::new("./favorites.json");
+fn update_day_display(ui_weak: slint::Weak<MainWindow>, day: &ForecastDay, units: Units) {
+    let temp = units.temp(day.temp);
+    let icon = day.icon.clone();
+    let conditions = day.conditions.clone();
+    let temp_min = units.temp(day.temp_min);
+    let temp_max = units.temp(day.temp_max);
+    let feels_like = units.temp(day.feels_like);
+    let precip = units.precip(day.precip);
+    let humidity = day.humidity;
+    let precip_prob = day.precip_prob;
+    let storm_risk = day
+        .severe_risk
+        .map_or("N/A".to_string(), |risk| format!("{risk}%"));
+    let snow = units.snow(day.snow);
+    let snow_depth = units.snow(day.snow_depth);
+    let wind_speed = units.speed(day.wind_speed);
+    let wind_dir = day.wind_dir;
+    let aqi_val = day.aqieur.unwrap_or(0.0);
+    let sunrise = day
+        .sunrise
+        .as_deref()
+        .map_or("None".to_string(), short_time);
+    let sunset = day.sunset.as_deref().map_or("None".to_string(), short_time);
+    let day_length = day.daylight.map_or("N/A".to_string(), hours_minutes);
+    let day_length_change = day
+        .daylight_change
+        .map_or("N/A".to_string(), signed_minutes_seconds);
+    let moon_phase = day.moon_phase.map_or("N/A", astro::moon_phase_name);
+    let moon_icon = day.moon_phase.map_or("", astro::moon_phase_icon);
 
-    match fs::read_to_string(path) {
-        Ok(file_content) => {
-            if !file_content.trim().is_empty() {
-                match serde_json::from_str(&file_content) {
-                    Ok(data) => {
-                        println!("Read successfully form favorites.json");
-                        data
-                    }
-                    Err(e) => {
-                        eprintln!("Error parsing favorites.json ({e}). Creating new list");
-                        Vec::new()
-                    }
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        ui.set_main_temp(SharedString::from(temp));
+        ui.set_main_icon(SharedString::from(icon));
+        ui.set_location_condition(SharedString::from(conditions));
+
+        ui.set_temp_min(SharedString::from(temp_min));
+        ui.set_temp_max(SharedString::from(temp_max));
+        ui.set_temp_feels_like(SharedString::from(feels_like));
+
+        ui.set_precip_amount(SharedString::from(precip));
+        ui.set_precip_humidity(humidity);
+        ui.set_precip_chance(precip_prob);
+        ui.set_storm_risk(SharedString::from(storm_risk));
+
+        ui.set_snow_amount(SharedString::from(snow));
+        ui.set_snow_depth_val(SharedString::from(snow_depth));
+
+        ui.set_wind_speed_val(SharedString::from(wind_speed));
+        ui.set_wind_dir_val(wind_dir);
+        ui.set_aqi_eur_val(aqi_val);
+
+        ui.set_sunrise(SharedString::from(sunrise));
+        ui.set_sunset(SharedString::from(sunset));
+        ui.set_day_length(SharedString::from(day_length));
+        ui.set_day_length_change(SharedString::from(day_length_change));
+        ui.set_moon_phase(SharedString::from(moon_phase));
+        ui.set_moon_icon(SharedString::from(moon_icon));
+    }) {
+        eprintln!("Failed to update day display: {e}")
+    }
+
+    update_hours(ui_weak.clone(), day, units);
+}
+
+// "Wind Warning +2" for the badge, empty without active alerts
+fn favorite_alert(current: &CurrentConditions) -> String {
+    let alerts = active_alerts(&current.alerts, Utc::now());
+    match alerts.first() {
+        Some(alert) if alerts.len() > 1 => format!("{} +{}", alert.event, alerts.len() - 1),
+        Some(alert) => alert.event.clone(),
+        None => String::new(),
+    }
+}
+
+/// Cards in saved order, with names and tags from `favorites` and the weather fetched
+/// for them; places that couldn't be loaded show the error and a retry button. Only
+/// the group picked in the panel is shown.
+fn update_favorites_panel(
+    ui_weak: slint::Weak<MainWindow>,
+    favorites: &[FavoriteLocation],
+    weather: &[FavoriteWeather],
+    units: Units,
+) {
+    let now = Utc::now();
+    let cards: Vec<(FavoritedLocation, Vec<String>)> = favorites
+        .iter()
+        .enumerate()
+        .map(|(index, favorite)| {
+            let mut card = FavoritedLocation {
+                index: index as i32,
+                icon: SharedString::from("default"),
+                name: SharedString::from(favorite.name()),
+                details: SharedString::from(favorite_details(favorite)),
+                tags: SharedString::from(favorite.tags.join(", ")),
+                ..Default::default()
+            };
+            let fetched = weather
+                .iter()
+                .find(|(_, fetched)| fetched.matches(favorite.lat, favorite.lon));
+            match fetched {
+                Some((Ok(current), _)) => {
+                    card.icon = SharedString::from(current.icon.clone());
+                    card.temp = SharedString::from(units.temp(current.temp));
+                    card.tempmin = SharedString::from(units.temp(current.temp_min));
+                    card.tempmax = SharedString::from(units.temp(current.temp_max));
+                    card.alert = SharedString::from(favorite_alert(current));
+                    card.alert_severity = SharedString::from(
+                        active_alerts(&current.alerts, now)
+                            .first()
+                            .map_or("", |alert| alert.severity.label()),
+                    );
                 }
-            } else {
-                Vec::new()
-            }
-        }
-        Err(e) => {
-            eprintln!("favorites.json doesn't exist ({e}). Creating.");
-            match OpenOptions::new().write(true).create_new(true).open(path) {
-                Ok(mut file) => match file.write_all(b"[]") {
-                    Ok(_) => {
-                        println!("Updated favorites.json with []")
-                    }
-                    Err(e) => {
-                        eprintln!("Error updating favorites.json with []: {e}")
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Failed to create favorites.json: {}", e);
+                Some((Err(e), _)) => {
+                    card.status = SharedString::from(e.user_message());
+                    card.failed = true;
                 }
+                // added since the last fetch, or being retried
+                None => card.status = SharedString::from("Loading…"),
             }
-            Vec::new()
+            (card, favorite.tags.clone())
+        })
+        .collect();
+    let groups = favorites::groups(favorites);
+
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let mut group = ui.get_favorite_group().to_string();
+        // the group was emptied or renamed away
+        if group != ALL_FAVORITES && !groups.contains(&group) {
+            group = ALL_FAVORITES.to_string();
+            ui.set_favorite_group(SharedString::from(ALL_FAVORITES));
         }
+
+        let shown: Vec<FavoritedLocation> = cards
+            .into_iter()
+            .filter(|(_, tags)| {
+                group == ALL_FAVORITES || tags.iter().any(|tag| tag.eq_ignore_ascii_case(&group))
+            })
+            .map(|(card, _)| card)
+            .collect();
+        let favorites_model = std::rc::Rc::new(slint::VecModel::from(shown));
+        ui.set_favorites(favorites_model.into());
+
+        let groups: Vec<SharedString> = std::iter::once(ALL_FAVORITES.to_string())
+            .chain(groups)
+            .map(SharedString::from)
+            .collect();
+        ui.set_favorite_groups(std::rc::Rc::new(slint::VecModel::from(groups)).into());
+    }) {
+        eprintln!("Failed to update favorites panel: {e}");
     }
 }
 
-fn add_to_favorites(location: FavoriteLocation) {
-    let mut favorites = read_favorites_from_file();
+// "Iași, Romania · Home, Ski trips": the provider's name when renamed, then the groups
+fn favorite_details(favorite: &FavoriteLocation) -> String {
+    let renamed = favorite
+        .display_name
+        .as_ref()
+        .map(|_| favorite.address.clone());
+    let tags = (!favorite.tags.is_empty()).then(|| favorite.tags.join(", "));
+    [renamed, tags]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
 
-    let already_exists = favorites
-        .iter()
-        .any(|fav| fav.matches(location.lat, location.lon));
+/// Renders the favorites panel again from what's already known, after an edit or a
+/// change of group or units
+async fn refresh_favorites_panel(
+    favorites: &Mutex<FavoritesStore>,
+    weather: &Mutex<Vec<FavoriteWeather>>,
+    units: &Mutex<Units>,
+    ui_weak: slint::Weak<MainWindow>,
+) {
+    let favorites = favorites.lock().await;
+    let weather = weather.lock().await;
+    let units = *units.lock().await;
+    update_favorites_panel(ui_weak, favorites.favorites(), &weather, units);
+}
 
-    if already_exists {
-        println!("'{}' is already favorited", location.address);
-        return;
-    }
+const ALL_FAVORITES: &str = "All favorites";
 
-    favorites.push(location);
-    write_favorites_to_file(favorites);
+// what's typed in the import/export box, with ~ for the home directory
+fn transfer_path(text: &str) -> Option<PathBuf> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    match text.strip_prefix("~/") {
+        Some(rest) => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(rest)),
+        None => Some(PathBuf::from(text)),
+    }
 }
 
-fn remove_from_favorites(lat: f64, lon: f64) {
-    let mut favorites = read_favorites_from_file();
-    favorites.retain(|fav| !fav.matches(lat, lon));
-    write_favorites_to_file(favorites);
+fn import_items(preview: &ImportPreview, saved: &[FavoriteLocation]) -> Vec<ImportItem> {
+    let new = preview.new.iter().map(|favorite| {
+        let coords = format_coords(favorite.lat, favorite.lon);
+        let details = [Some(coords), Some(favorite.tags.join(", "))]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" · ");
+        ImportItem {
+            name: SharedString::from(favorite.name()),
+            details: SharedString::from(details),
+            duplicate: false,
+        }
+    });
+    let duplicates = preview
+        .duplicates
+        .iter()
+        .map(|(favorite, index)| ImportItem {
+            name: SharedString::from(favorite.name()),
+            details: SharedString::from(format!("already saved as {}", saved[*index].name())),
+            duplicate: true,
+        });
+    new.chain(duplicates).collect()
 }
 
-fn is_location_favorited(favorites: &[FavoriteLocation], lat: f64, lon: f64) -> bool {
-    favorites.iter().any(|fav| fav.matches(lat, lon))
+fn show_transfer_status(ui_weak: slint::Weak<MainWindow>, status: String, items: Vec<ImportItem>) {
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        ui.set_favorites_transfer_status(SharedString::from(status));
+        let items_model = std::rc::Rc::new(slint::VecModel::from(items));
+        ui.set_favorites_import_items(items_model.into());
+    }) {
+        eprintln!("Failed to update import status: {e}");
+    }
 }
 
-async fn display_weather_info(
-    input_location: String,
+fn update_rules_list(
     ui_weak: slint::Weak<MainWindow>,
-) -> Result<ForecastResponse, anyhow::Error> {
-    let value = get_city_weather_info(input_location.clone()).await?;
-    let today = value
-        .days
-        .first()
-        .ok_or_else(|| anyhow::anyhow!("API returned no forecast days"))?;
-
-    let temp = today.temp;
-    let icon = today.icon.clone();
-    let address = value.resolved_address.clone();
-    let conditions = today.conditions.clone();
-    let temp_min = today.temp_min;
-    let temp_max = today.temp_max;
-    let feels_like = today.feels_like;
-    let precip = today.precip;
-    let humidity = today.humidity;
-    let precip_prob = today.precip_prob;
-    let snow = today.snow;
-    let snow_depth = today.snow_depth;
-    let wind_speed = today.wind_speed;
-    let wind_dir = today.wind_dir;
-    let aqi_val = today.aqieur.unwrap_or(0.0);
-
-    let forecast_days: Vec<Day> = value
-        .days
+    rules: &[Rule],
+    favorites: &[FavoriteLocation],
+    units: Units,
+) {
+    let items: Vec<RuleItem> = rules
         .iter()
-        .map(|day| Day {
-            datetime: SharedString::from(date_to_day(day.datetime.clone())),
-            temp: day.temp,
-            temp_max: day.temp_max,
-            temp_min: day.temp_min,
+        .map(|rule| RuleItem {
+            description: SharedString::from(rule.describe(&units, favorites)),
+            enabled: rule.enabled,
         })
         .collect();
 
-    match ui_weak.upgrade_in_event_loop(move |ui| {
-        ui.set_main_temp(temp);
-        ui.set_main_icon(SharedString::from(icon));
-        ui.set_location_name(SharedString::from(address));
-        ui.set_location_condition(SharedString::from(conditions));
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let rules_model = std::rc::Rc::new(slint::VecModel::from(items));
+        ui.set_rules(rules_model.into());
+    }) {
+        eprintln!("Failed to update rules list: {e}");
+    }
+}
 
-        ui.set_temp_min(temp_min);
-        ui.set_temp_max(temp_max);
-        ui.set_temp_feels_like(feels_like);
+fn update_rule_matches(ui_weak: slint::Weak<MainWindow>, matches: &[RuleMatch], units: Units) {
+    let items: Vec<RuleMatchItem> = matches
+        .iter()
+        .map(|rule_match| RuleMatchItem {
+            summary: SharedString::from(rule_match.summary()),
+            details: SharedString::from(rule_match.details(&units)),
+        })
+        .collect();
 
-        ui.set_precip_amount(precip);
-        ui.set_precip_humidity(humidity);
-        ui.set_precip_chance(precip_prob);
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let matches_model = std::rc::Rc::new(slint::VecModel::from(items));
+        ui.set_rule_matches(matches_model.into());
+    }) {
+        eprintln!("Failed to update rule matches: {e}");
+    }
+}
 
-        ui.set_snow_amount(snow);
-        ui.set_snow_depth_val(snow_depth);
+/// A failed save keeps the change for this session and says so
+fn save_rules(ui_weak: slint::Weak<MainWindow>, config: &Config, rules: &[Rule]) {
+    if let Err(e) = write_rules_to_file(config, rules) {
+        eprintln!("Failed to save rules: {e}");
+        show_error_toast(ui_weak, format!("Rules couldn't be saved: {e}"));
+    }
+}
 
-        ui.set_wind_speed_val(wind_speed);
-        ui.set_wind_dir_val(wind_dir);
-        ui.set_aqi_eur_val(aqi_val);
+/// Favorites are listed by the name shown in the panel, and picked by the address
+/// rules are keyed by (empty for every favorite)
+fn update_rule_locations(ui_weak: slint::Weak<MainWindow>, favorites: &[FavoriteLocation]) {
+    let locations: Vec<SharedString> = std::iter::once(ALL_FAVORITES)
+        .chain(favorites.iter().map(FavoriteLocation::name))
+        .map(SharedString::from)
+        .collect();
+    let keys: Vec<SharedString> = std::iter::once("")
+        .chain(favorites.iter().map(|fav| fav.address.as_str()))
+        .map(SharedString::from)
+        .collect();
 
-        let days_model = std::rc::Rc::new(slint::VecModel::from(forecast_days));
-        ui.set_days(days_model.into());
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let locations_model = std::rc::Rc::new(slint::VecModel::from(locations));
+        ui.set_rule_locations(locations_model.into());
+        let keys_model = std::rc::Rc::new(slint::VecModel::from(keys));
+        ui.set_rule_location_keys(keys_model.into());
     }) {
-        Ok(_) => println!("Updated UI successfully!"),
-        Err(e) => eprintln!("Failed to update the UI: {e}"),
+        eprintln!("Failed to update rule locations: {e}");
     }
+}
 
-    update_hours(ui_weak.clone(), today);
+/// Builds a rule from the rules panel form. The threshold is typed in display units.
+#[allow(clippy::too_many_arguments)]
+fn parse_rule(
+    metric: &str,
+    comparison: &str,
+    threshold: &str,
+    day: &str,
+    from: &str,
+    to: &str,
+    location: &str,
+    units: &Units,
+) -> Result<Rule, String> {
+    let metric = Metric::from_label(metric).ok_or("Pick what the rule should check")?;
+    let comparison = Comparison::from_label(comparison).ok_or("Pick below or above")?;
+    let day = DaySelector::from_label(day).ok_or("Pick which day the rule is for")?;
+    let threshold: f32 = threshold.trim().replace(',', ".").parse().map_err(|_| {
+        format!(
+            "Enter a number for the threshold ({})",
+            metric.unit_symbol(units)
+        )
+    })?;
+
+    let hours = match (from.trim(), to.trim()) {
+        ("", "") => None,
+        (from, to) => {
+            let from: u8 = from.parse().map_err(|_| "Hours must be whole numbers")?;
+            let to: u8 = to.parse().map_err(|_| "Hours must be whole numbers")?;
+            if from >= to || to > 24 {
+                return Err("Hours must be between 0 and 24, with 'from' before 'to'".to_string());
+            }
+            Some(HourWindow { from, to })
+        }
+    };
 
-    Ok(value)
+    Ok(Rule {
+        metric,
+        comparison,
+        threshold: metric.from_display(units, threshold),
+        day,
+        hours,
+        location: (!location.is_empty()).then(|| location.to_string()),
+        enabled: true,
+    })
 }
 
-fn update_day_display(ui_weak: slint::Weak<MainWindow>, day: &ForecastDay) {
-    let temp = day.temp;
-    let icon = day.icon.clone();
-    let conditions = day.conditions.clone();
-    let temp_min = day.temp_min;
-    let temp_max = day.temp_max;
-    let feels_like = day.feels_like;
-    let precip = day.precip;
-    let humidity = day.humidity;
-    let precip_prob = day.precip_prob;
-    let snow = day.snow;
-    let snow_depth = day.snow_depth;
-    let wind_speed = day.wind_speed;
-    let wind_dir = day.wind_dir;
-    let aqi_val = day.aqieur.unwrap_or(0.0);
+/// Runs the rules against every favorite's forecast, notifying new matches
+async fn check_rules(
+    provider: &dyn WeatherProvider,
+    favorites: &[FavoriteLocation],
+    rules: &[Rule],
+    engine: &mut RuleEngine,
+    units: Units,
+) -> Vec<RuleMatch> {
+    if !rules.iter().any(|rule| rule.enabled) {
+        return Vec::new();
+    }
 
-    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
-        ui.set_main_temp(temp);
-        ui.set_main_icon(SharedString::from(icon));
-        ui.set_location_condition(SharedString::from(conditions));
+    let mut matches = Vec::new();
+    for favorite in favorites {
+        let forecast = match provider
+            .forecast_by_coords(favorite.lat, favorite.lon)
+            .await
+        {
+            Ok(forecast) => forecast,
+            Err(e) => {
+                eprintln!("Rule check skipped {}: {e}", favorite.address);
+                continue;
+            }
+        };
 
-        ui.set_temp_min(temp_min);
-        ui.set_temp_max(temp_max);
-        ui.set_temp_feels_like(feels_like);
+        // sending a notification is a blocking D-Bus call
+        let found =
+            tokio::task::block_in_place(|| engine.check(rules, favorite, &forecast, &units));
+        matches.extend(found);
+    }
 
-        ui.set_precip_amount(precip);
-        ui.set_precip_humidity(humidity);
-        ui.set_precip_chance(precip_prob);
+    matches
+}
 
-        ui.set_snow_amount(snow);
-        ui.set_snow_depth_val(snow_depth);
+/// Checks the rules every `interval` (never when it's zero) and whenever `check_now` fires
+#[allow(clippy::too_many_arguments)]
+fn spawn_rule_checker(
+    provider: Arc<dyn WeatherProvider>,
+    interval: Duration,
+    check_now: Arc<Notify>,
+    rules: Arc<Mutex<Vec<Rule>>>,
+    favorites: Arc<Mutex<FavoritesStore>>,
+    last_matches: Arc<Mutex<Vec<RuleMatch>>>,
+    units: Arc<Mutex<Units>>,
+    ui_weak: slint::Weak<MainWindow>,
+) {
+    let mut engine = RuleEngine::new(Arc::new(DesktopNotifier));
 
-        ui.set_wind_speed_val(wind_speed);
-        ui.set_wind_dir_val(wind_dir);
-        ui.set_aqi_eur_val(aqi_val);
+    tokio::spawn(async move {
+        // on a schedule, check right away instead of a full interval after startup
+        let mut wait = interval.is_zero();
+        loop {
+            if wait {
+                if interval.is_zero() {
+                    check_now.notified().await;
+                } else {
+                    tokio::select! {
+                        _ = tokio::time::sleep(interval) => {}
+                        _ = check_now.notified() => {}
+                    }
+                }
+            }
+            wait = true;
+
+            let rules = rules.lock().await.clone();
+            let favorites = favorites.lock().await.favorites().to_vec();
+            let units = *units.lock().await;
+            let matches =
+                check_rules(provider.as_ref(), &favorites, &rules, &mut engine, units).await;
+
+            update_rule_matches(ui_weak.clone(), &matches, units);
+            let label = format!("Checked {}", Local::now().format("%H:%M"));
+            if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+                ui.set_rules_last_checked(SharedString::from(label));
+            }) {
+                eprintln!("Failed to update rules check time: {e}");
+            }
+            *last_matches.lock().await = matches;
+        }
+    });
+}
+
+/// Starts searches so that only the newest one ever reaches the UI: older ones are
+/// aborted, or dropped if they already got their answer.
+#[derive(Clone)]
+struct Searcher {
+    provider: Arc<dyn WeatherProvider>,
+    cities: Option<Arc<CityIndex>>,
+    generation: Arc<AtomicU64>,
+    task: Arc<std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    last_query: Arc<Mutex<Option<Query>>>,
+    last_forecast: Arc<Mutex<Option<ForecastResponse>>>,
+    last_fav: Arc<Mutex<FavoritesStore>>,
+    units: Arc<Mutex<Units>>,
+    history: Arc<std::sync::Mutex<SearchHistory>>,
+    ui_weak: slint::Weak<MainWindow>,
+}
+
+impl Searcher {
+    fn start(&self, query: Query) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Err(e) = self
+            .ui_weak
+            .upgrade_in_event_loop(|ui| ui.set_loading(true))
+        {
+            eprintln!("Failed to show loading state: {e}");
+        }
+
+        let searcher = self.clone();
+        let task = tokio::spawn(async move {
+            let result = query
+                .fetch(searcher.provider.as_ref(), searcher.cities.as_deref())
+                .await;
+
+            // take every lock before checking the generation, so an abort can't land halfway
+            // through updating the UI and the shared state
+            let mut last = searcher.last_forecast.lock().await;
+            let mut last_query = searcher.last_query.lock().await;
+            let favorites = searcher.last_fav.lock().await;
+            let units = *searcher.units.lock().await;
+            if searcher.generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+
+            let ui_weak = searcher.ui_weak.clone();
+            let result = result.and_then(|forecast| {
+                show_forecast(ui_weak.clone(), &forecast, units)?;
+                Ok(forecast)
+            });
+            let is_fav = match result {
+                Ok(forecast) => {
+                    let is_fav = favorites.contains(forecast.latitude, forecast.longitude);
+                    searcher.remember(&forecast);
+                    *last_query = Some(query);
+                    *last = Some(forecast);
+                    Some(is_fav)
+                }
+                Err(e) => {
+                    show_error_toast(ui_weak.clone(), e.user_message());
+                    eprintln!("Error at search for {query}: {e}");
+                    None
+                }
+            };
+
+            if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+                ui.set_loading(false);
+                if let Some(is_fav) = is_fav {
+                    ui.set_selected_day_index(0);
+                    ui.set_is_favorited(is_fav);
+                }
+            }) {
+                eprintln!("Failed to update display after search: {e}");
+            }
+        });
+
+        let previous = match self.task.lock() {
+            Ok(mut slot) => slot.replace(task),
+            Err(e) => {
+                eprintln!("Search task slot poisoned: {e}");
+                None
+            }
+        };
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+    }
+
+    /// Adds the search to the history. Saving it happens on a blocking thread, so the
+    /// search task's locks aren't held while the file is written.
+    fn remember(&self, forecast: &ForecastResponse) {
+        let history = self.history.clone();
+        let ui_weak = self.ui_weak.clone();
+        let (address, lat, lon) = (
+            forecast.resolved_address.clone(),
+            forecast.latitude,
+            forecast.longitude,
+        );
+        tokio::task::spawn_blocking(move || match history.lock() {
+            Ok(mut history) => {
+                history.record(address, lat, lon, Utc::now());
+                history.save();
+                update_recent_searches(ui_weak, &history);
+            }
+            Err(e) => eprintln!("Search history poisoned: {e}"),
+        });
+    }
+}
+
+// "Idaho, United States · 46.73, -117.00"
+fn suggestion_details(place: &Place) -> String {
+    let area = [place.region.as_deref(), place.country.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let coords = format!("{:.2}, {:.2}", place.lat, place.lon);
+    if area.is_empty() {
+        coords
+    } else {
+        format!("{area} · {coords}")
+    }
+}
+
+fn update_suggestions(ui_weak: slint::Weak<MainWindow>, places: &[Place]) {
+    let suggestions: Vec<Suggestion> = places
+        .iter()
+        .map(|place| Suggestion {
+            name: SharedString::from(place.name.clone()),
+            details: SharedString::from(suggestion_details(place)),
+        })
+        .collect();
+
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let suggestions_model = std::rc::Rc::new(slint::VecModel::from(suggestions));
+        ui.set_suggestions(suggestions_model.into());
     }) {
-        eprintln!("Failed to update day display: {e}")
+        eprintln!("Failed to update search suggestions: {e}");
     }
+}
 
-    update_hours(ui_weak.clone(), day);
+fn update_recent_searches(ui_weak: slint::Weak<MainWindow>, history: &SearchHistory) {
+    let now = Utc::now();
+    let recent: Vec<Suggestion> = history
+        .entries()
+        .iter()
+        .map(|entry| Suggestion {
+            name: SharedString::from(entry.address.clone()),
+            details: SharedString::from(format!(
+                "{:.2}, {:.2} · {}",
+                entry.lat,
+                entry.lon,
+                time_ago(entry.searched_at, now)
+            )),
+        })
+        .collect();
+
+    if let Err(e) = ui_weak.upgrade_in_event_loop(move |ui| {
+        let recent_model = std::rc::Rc::new(slint::VecModel::from(recent));
+        ui.set_recent_searches(recent_model.into());
+    }) {
+        eprintln!("Failed to update recent searches: {e}");
+    }
+}
+
+fn slint_strings<const N: usize>(labels: [&str; N]) -> slint::ModelRc<SharedString> {
+    let labels: Vec<SharedString> = labels.into_iter().map(SharedString::from).collect();
+    std::rc::Rc::new(slint::VecModel::from(labels)).into()
 }
 
 fn show_error_toast(ui_weak: slint::Weak<MainWindow>, message: String) {
@@ -407,188 +944,793 @@ fn show_error_toast(ui_weak: slint::Weak<MainWindow>, message: String) {
 slint::include_modules!();
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+    let config = Config::load(args.config.as_deref(), args.config_layer())?;
+    if args.export.is_some() || args.import.is_some() {
+        return cli::transfer(&args, &config);
+    }
+    let client = http::client_from_config(&config)?;
+    let provider: Arc<dyn WeatherProvider> = Arc::from(providers::from_config(&config, &client)?);
+
+    let cities = CityIndex::from_config(&config);
+
+    if args.cli {
+        return cli::run(&args, &config, provider.as_ref(), cities.as_deref()).await;
+    }
+
     let main_window = MainWindow::new()?;
     let ui_weak = main_window.as_weak();
+    let geocoder: Arc<dyn Geocoder> =
+        Arc::from(geocoding::from_config(&config, &client, cities.clone()));
 
-    let default_location = "Iasi".to_string();
+    let default_location = config.default_location.clone();
 
     let last_forecast: Arc<Mutex<Option<ForecastResponse>>> = Arc::new(Mutex::new(None));
-    let last_fav: Arc<Mutex<Vec<FavoriteLocation>>> = Arc::new(Mutex::new(Vec::new()));
-
-    match display_weather_info(default_location.clone(), ui_weak.clone()).await {
+    // what was searched for to get `last_forecast`, used to fetch it again
+    let startup_query = Query::from_text(default_location.clone(), cities.as_deref())
+        .unwrap_or_else(|message| {
+            eprintln!("Default location {default_location}: {message}");
+            Query::Text(default_location.clone())
+        });
+    let last_query: Arc<Mutex<Option<Query>>> = Arc::new(Mutex::new(Some(startup_query.clone())));
+    let favorites_store = match FavoritesStore::from_config(&config) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Failed to load favorites: {e}");
+            show_error_toast(
+                ui_weak.clone(),
+                format!("Favorites couldn't be loaded: {e}"),
+            );
+            FavoritesStore::unsaved()
+        }
+    };
+    let last_fav: Arc<Mutex<FavoritesStore>> = Arc::new(Mutex::new(favorites_store));
+    let last_fav_weather: Arc<Mutex<Vec<FavoriteWeather>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let unit_overrides = config.unit_overrides;
+    let units = Arc::new(Mutex::new(config.display_units()));
+    main_window.set_unit_system(SharedString::from(config.units.label()));
+
+    match display_weather_info(
+        provider.as_ref(),
+        cities.as_deref(),
+        &startup_query,
+        ui_weak.clone(),
+        config.display_units(),
+    )
+    .await
+    {
         Ok(forecast) => {
-            let lat = forecast.latitude;
-            let lon = forecast.longitude;
-
+            let is_fav = last_fav
+                .lock()
+                .await
+                .contains(forecast.latitude, forecast.longitude);
+            main_window.set_is_favorited(is_fav);
             *last_forecast.lock().await = Some(forecast);
-            *last_fav.lock().await = read_favorites_from_file();
+        }
+        Err(e) => {
+            // still open the window, the user can search for something else or retry
+            eprintln!("Failed to load {default_location}: {e}");
+            show_error_toast(ui_weak.clone(), e.user_message());
+        }
+    }
 
-            let is_fav = is_location_favorited(&last_fav.lock().await, lat, lon);
-            main_window.set_is_favorited(is_fav);
+    let rules = match read_rules_from_file(&config) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Failed to load rules: {e}");
+            show_error_toast(ui_weak.clone(), format!("Rules couldn't be loaded: {e}"));
+            Vec::new()
+        }
+    };
+    let rules: Arc<Mutex<Vec<Rule>>> = Arc::new(Mutex::new(rules));
+    // where rules are saved, see `write_rules_to_file`
+    let rules_config = Arc::new(config.clone());
+    let rule_matches: Arc<Mutex<Vec<RuleMatch>>> = Arc::new(Mutex::new(Vec::new()));
+    let check_rules_now = Arc::new(Notify::new());
+
+    main_window.set_rule_metrics(slint_strings(Metric::ALL.map(Metric::label)));
+    main_window.set_rule_comparisons(slint_strings(Comparison::ALL.map(Comparison::label)));
+    main_window.set_rule_days(slint_strings(DaySelector::ALL.map(DaySelector::label)));
+    update_rules_list(
+        ui_weak.clone(),
+        &rules.lock().await,
+        last_fav.lock().await.favorites(),
+        config.display_units(),
+    );
+    update_rule_locations(ui_weak.clone(), last_fav.lock().await.favorites());
+
+    spawn_rule_checker(
+        provider.clone(),
+        Duration::from_secs(config.rules_check_minutes * 60),
+        check_rules_now.clone(),
+        rules.clone(),
+        last_fav.clone(),
+        rule_matches.clone(),
+        units.clone(),
+        ui_weak.clone(),
+    );
 
-            let last_forecast_for_day_select = last_forecast.clone();
-            let last_forecast_for_search = last_forecast.clone();
-            let last_forecast_for_fav = last_forecast.clone();
+    spawn_refresh_scheduler(
+        provider.clone(),
+        cities.clone(),
+        Duration::from_secs(config.refresh_minutes * 60),
+        last_query.clone(),
+        last_forecast.clone(),
+        last_fav.clone(),
+        last_fav_weather.clone(),
+        units.clone(),
+        ui_weak.clone(),
+    );
+
+    let last_forecast_for_day_select = last_forecast.clone();
+    let last_forecast_for_fav = last_forecast.clone();
+    let last_forecast_for_units = last_forecast.clone();
+
+    let last_fav_for_fav = last_fav.clone();
+    let last_fav_for_fav_panel = last_fav.clone();
+
+    let last_fav_weather_for_fav_panel = last_fav_weather.clone();
+    let last_fav_weather_for_units = last_fav_weather.clone();
+    let last_fav_for_rules = last_fav.clone();
+    let last_fav_for_units = last_fav.clone();
+
+    let units_for_day_select = units.clone();
+    let units_for_fav_panel = units.clone();
+    let units_for_units = units.clone();
+    let units_for_rules = units.clone();
+
+    let rules_for_units = rules.clone();
+    let rule_matches_for_units = rule_matches.clone();
+
+    // DAY SELECTED LOGIC
+    let ui_weak_for_day = ui_weak.clone();
+    main_window.on_day_selected(move |index| {
+        let i = index as usize;
+        let ui_weak_clone = ui_weak_for_day.clone();
+        let forecast_clone = last_forecast_for_day_select.clone();
+        let units_clone = units_for_day_select.clone();
+
+        tokio::spawn(async move {
+            let units = *units_clone.lock().await;
+            if let Some(forecast) = forecast_clone.lock().await.as_ref()
+                && i < forecast.days.len()
+            {
+                update_day_display(ui_weak_clone, &forecast.days[i], units);
+            }
+        });
+    });
 
-            let last_fav_for_search = last_fav.clone();
-            let last_fav_for_fav = last_fav.clone();
-            let last_fav_for_fav_panel = last_fav.clone();
+    // SEARCH LOGIC
+    let history = Arc::new(std::sync::Mutex::new(SearchHistory::from_config(&config)));
+    if let Ok(history) = history.lock() {
+        update_recent_searches(ui_weak.clone(), &history);
+    }
+    let searcher = Searcher {
+        provider: provider.clone(),
+        cities: cities.clone(),
+        generation: Arc::new(AtomicU64::new(0)),
+        task: Arc::new(std::sync::Mutex::new(None)),
+        last_query: last_query.clone(),
+        last_forecast: last_forecast.clone(),
+        last_fav: last_fav.clone(),
+        units: units.clone(),
+        history: history.clone(),
+        ui_weak: ui_weak.clone(),
+    };
+    // bumped on every keystroke and search, so late suggestions don't pop up again
+    let suggest_generation = Arc::new(AtomicU64::new(0));
+    let suggested_places: Arc<std::sync::Mutex<Vec<Place>>> =
+        Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let ui_weak_for_search = ui_weak.clone();
+    let searcher_for_search = searcher.clone();
+    let suggest_generation_for_search = suggest_generation.clone();
+    main_window.on_invoke_api(move |input| {
+        let location = input.to_string().trim().to_string();
+
+        if location.is_empty() {
+            show_error_toast(
+                ui_weak_for_search.clone(),
+                "Enter a location before searching for one".to_string(),
+            );
+            return;
+        }
 
-            // DAY SELECTED LOGIC
-            let ui_weak_for_day = ui_weak.clone();
-            main_window.on_day_selected(move |index| {
-                let i = index as usize;
-                let ui_weak_clone = ui_weak_for_day.clone();
-                let forecast_clone = last_forecast_for_day_select.clone();
+        suggest_generation_for_search.fetch_add(1, Ordering::SeqCst);
+        // only names get capitalized, coordinates stay as typed
+        let query = match Query::from_text(location, searcher_for_search.cities.as_deref()) {
+            Ok(Query::Text(location)) => Query::Text(capitalize_first(location)),
+            Ok(query) => query,
+            Err(message) => {
+                show_error_toast(ui_weak_for_search.clone(), message);
+                return;
+            }
+        };
+        searcher_for_search.start(query);
+    });
 
-                tokio::spawn(async move {
-                    if let Some(forecast) = forecast_clone.lock().await.as_ref()
-                        && i < forecast.days.len()
-                    {
-                        update_day_display(ui_weak_clone, &forecast.days[i]);
+    // SUGGESTIONS LOGIC
+    const MIN_SUGGEST_CHARS: usize = 2;
+    const SUGGEST_DEBOUNCE: Duration = Duration::from_millis(300);
+    const MAX_SUGGESTIONS: usize = 6;
+
+    let ui_weak_for_suggest = ui_weak.clone();
+    let suggest_generation_for_edit = suggest_generation.clone();
+    let suggested_places_for_edit = suggested_places.clone();
+    main_window.on_search_edited(move |text| {
+        let text = text.trim().to_string();
+        let generation = suggest_generation_for_edit.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let ui_weak_clone = ui_weak_for_suggest.clone();
+        let generation_clone = suggest_generation_for_edit.clone();
+        let places_clone = suggested_places_for_edit.clone();
+        let geocoder_clone = geocoder.clone();
+
+        tokio::spawn(async move {
+            let places = if text.chars().count() < MIN_SUGGEST_CHARS {
+                Vec::new()
+            } else {
+                // wait for the user to stop typing
+                tokio::time::sleep(SUGGEST_DEBOUNCE).await;
+                if generation_clone.load(Ordering::SeqCst) != generation {
+                    return;
+                }
+                match geocoder_clone.search(&text, MAX_SUGGESTIONS).await {
+                    Ok(places) => places,
+                    Err(e) => {
+                        eprintln!("Failed to get suggestions for {text}: {e}");
+                        return;
                     }
-                });
-            });
+                }
+            };
 
-            // SEARCH LOGIC
-            let ui_weak_for_search = ui_weak.clone();
+            if generation_clone.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            update_suggestions(ui_weak_clone, &places);
+            if let Ok(mut suggested) = places_clone.lock() {
+                *suggested = places;
+            }
+        });
+    });
 
-            main_window.on_invoke_api(move |input| {
-                let mut location = input.to_string().trim().to_string();
+    let searcher_for_recent = searcher.clone();
+    let searcher_for_favorites = searcher.clone();
+    let history_for_recent = history.clone();
+    main_window.on_recent_chosen(move |index| {
+        let entry = match history_for_recent.lock() {
+            Ok(history) => history.entries().get(index as usize).cloned(),
+            Err(e) => {
+                eprintln!("Search history poisoned: {e}");
+                None
+            }
+        };
+        // fetched by coordinates, so it comes back as the same place under the same name
+        if let Some(entry) = entry {
+            searcher_for_recent.start(Query::Place(Place {
+                name: entry.address,
+                region: None,
+                country: None,
+                lat: entry.lat,
+                lon: entry.lon,
+            }));
+        }
+    });
 
-                if location.is_empty() {
-                    show_error_toast(
-                        ui_weak_for_search.clone(),
-                        "Enter a location before searching for one".to_string(),
+    let ui_weak_for_history = ui_weak.clone();
+    main_window.on_clear_history(move || {
+        let history = history.clone();
+        let ui_weak = ui_weak_for_history.clone();
+        tokio::task::spawn_blocking(move || match history.lock() {
+            Ok(mut history) => {
+                history.clear();
+                history.save();
+                update_recent_searches(ui_weak, &history);
+            }
+            Err(e) => eprintln!("Search history poisoned: {e}"),
+        });
+    });
+
+    main_window.on_suggestion_chosen(move |index| {
+        suggest_generation.fetch_add(1, Ordering::SeqCst);
+        let place = match suggested_places.lock() {
+            Ok(places) => places.get(index as usize).cloned(),
+            Err(e) => {
+                eprintln!("Suggestion list poisoned: {e}");
+                None
+            }
+        };
+        if let Some(place) = place {
+            searcher.start(Query::Place(place));
+        }
+    });
+
+    // FAVORITE LOGIC
+    let ui_weak_for_fav = ui_weak.clone();
+    let cities_for_fav = cities.clone();
+    main_window.on_toggle_fav(move |_location_name, fav| {
+        let fav_clone = last_fav_for_fav.clone();
+        let forecast_clone = last_forecast_for_fav.clone();
+        let ui_weak_clone = ui_weak_for_fav.clone();
+        let cities_clone = cities_for_fav.clone();
+
+        tokio::spawn(async move {
+            if let Some(forecast) = forecast_clone.lock().await.as_ref() {
+                let lat = forecast.latitude;
+                let lon = forecast.longitude;
+                // saved under the city list's name when it's in a listed city, so it
+                // doesn't depend on which provider answered the search
+                let address = cities_clone
+                    .as_deref()
+                    .and_then(|cities| cities.city_at(lat, lon))
+                    .map_or_else(
+                        || forecast.resolved_address.clone(),
+                        |city| city.display_name(),
                     );
+
+                let mut favorites = fav_clone.lock().await;
+                let result = if fav {
+                    favorites.add(FavoriteLocation::new(address, lat, lon))
+                } else {
+                    favorites.remove(lat, lon)
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to save favorites: {e}");
+                    show_error_toast(ui_weak_clone, format!("Favorites couldn't be saved: {e}"));
+                }
+            }
+        });
+    });
+
+    // FAVORITE PANEL LOGIC
+    let ui_weak_for_fav_panel = ui_weak.clone();
+    let provider_for_fav_panel = provider.clone();
+    main_window.on_invoke_favorites_api(move || {
+        let fav_clone = last_fav_for_fav_panel.clone();
+        let ui_weak_clone = ui_weak_for_fav_panel.clone();
+        let provider_clone = provider_for_fav_panel.clone();
+        let fav_weather_clone = last_fav_weather_for_fav_panel.clone();
+        let units_clone = units_for_fav_panel.clone();
+
+        tokio::spawn(async move {
+            let favorites_list = fav_clone.lock().await.favorites().to_vec();
+            let (results, first_error) =
+                fetch_favorites_weather(provider_clone, favorites_list).await;
+            if let Some(e) = first_error {
+                show_error_toast(
+                    ui_weak_clone.clone(),
+                    format!("Some favorites couldn't be loaded. {}", e.user_message()),
+                );
+            }
+
+            *fav_weather_clone.lock().await = results;
+            refresh_favorites_panel(&fav_clone, &fav_weather_clone, &units_clone, ui_weak_clone)
+                .await;
+        });
+    });
+
+    // opened by coordinates, so a renamed favorite still finds the right place
+    let last_fav_for_open = last_fav.clone();
+    main_window.on_favorite_opened(move |index| {
+        let favorites = last_fav_for_open.clone();
+        let searcher = searcher_for_favorites.clone();
+        tokio::spawn(async move {
+            let Some(favorite) = favorites
+                .lock()
+                .await
+                .favorites()
+                .get(index as usize)
+                .cloned()
+            else {
+                return;
+            };
+            searcher.start(Query::Place(Place {
+                name: favorite.name().to_string(),
+                region: None,
+                country: None,
+                lat: favorite.lat,
+                lon: favorite.lon,
+            }));
+        });
+    });
+
+    // one failed card, fetched again on its own
+    let ui_weak_for_retry = ui_weak.clone();
+    let last_fav_for_retry = last_fav.clone();
+    let last_fav_weather_for_retry = last_fav_weather.clone();
+    let units_for_retry = units.clone();
+    let provider_for_retry = provider.clone();
+    main_window.on_retry_favorite(move |index| {
+        let ui_weak_clone = ui_weak_for_retry.clone();
+        let favorites = last_fav_for_retry.clone();
+        let weather = last_fav_weather_for_retry.clone();
+        let units = units_for_retry.clone();
+        let provider = provider_for_retry.clone();
+        tokio::spawn(async move {
+            let Some(favorite) = favorites
+                .lock()
+                .await
+                .favorites()
+                .get(index as usize)
+                .cloned()
+            else {
+                return;
+            };
+            let forget = |weather: &mut Vec<FavoriteWeather>| {
+                weather.retain(|(_, fetched)| !fetched.matches(favorite.lat, favorite.lon));
+            };
+
+            forget(&mut *weather.lock().await);
+            refresh_favorites_panel(&favorites, &weather, &units, ui_weak_clone.clone()).await;
+
+            let (results, error) = fetch_favorites_weather(provider, vec![favorite.clone()]).await;
+            if let Some(e) = error {
+                show_error_toast(
+                    ui_weak_clone.clone(),
+                    format!(
+                        "{} couldn't be loaded. {}",
+                        favorite.name(),
+                        e.user_message()
+                    ),
+                );
+            }
+            {
+                let mut weather = weather.lock().await;
+                forget(&mut weather);
+                weather.extend(results);
+            }
+            refresh_favorites_panel(&favorites, &weather, &units, ui_weak_clone).await;
+        });
+    });
+
+    let ui_weak_for_edit = ui_weak.clone();
+    let last_fav_for_edit = last_fav.clone();
+    let last_fav_weather_for_edit = last_fav_weather.clone();
+    let units_for_edit = units.clone();
+    main_window.on_edit_favorite(move |index, name, tags| {
+        let ui_weak_clone = ui_weak_for_edit.clone();
+        let favorites = last_fav_for_edit.clone();
+        let weather = last_fav_weather_for_edit.clone();
+        let units = units_for_edit.clone();
+        tokio::spawn(async move {
+            let tags: Vec<&str> = tags.split(',').collect();
+            let result = favorites.lock().await.edit(index as usize, &name, &tags);
+            if let Err(e) = result {
+                eprintln!("Failed to save favorites: {e}");
+                show_error_toast(
+                    ui_weak_clone.clone(),
+                    format!("Favorites couldn't be saved: {e}"),
+                );
+            }
+            refresh_favorites_panel(&favorites, &weather, &units, ui_weak_clone).await;
+        });
+    });
+
+    let ui_weak_for_move = ui_weak.clone();
+    let last_fav_for_move = last_fav.clone();
+    let last_fav_weather_for_move = last_fav_weather.clone();
+    let units_for_move = units.clone();
+    main_window.on_move_favorite(move |index, up, group| {
+        let ui_weak_clone = ui_weak_for_move.clone();
+        let favorites = last_fav_for_move.clone();
+        let weather = last_fav_weather_for_move.clone();
+        let units = units_for_move.clone();
+        tokio::spawn(async move {
+            let group = (group != ALL_FAVORITES).then_some(group.as_str());
+            let result = favorites
+                .lock()
+                .await
+                .move_favorite(index as usize, up, group);
+            if let Err(e) = result {
+                eprintln!("Failed to save favorites: {e}");
+                show_error_toast(
+                    ui_weak_clone.clone(),
+                    format!("Favorites couldn't be saved: {e}"),
+                );
+            }
+            refresh_favorites_panel(&favorites, &weather, &units, ui_weak_clone).await;
+        });
+    });
+
+    let ui_weak_for_group = ui_weak.clone();
+    let last_fav_for_group = last_fav.clone();
+    let last_fav_weather_for_group = last_fav_weather.clone();
+    let units_for_group = units.clone();
+    main_window.on_favorite_group_changed(move |_group| {
+        let ui_weak_clone = ui_weak_for_group.clone();
+        let favorites = last_fav_for_group.clone();
+        let weather = last_fav_weather_for_group.clone();
+        let units = units_for_group.clone();
+        tokio::spawn(async move {
+            refresh_favorites_panel(&favorites, &weather, &units, ui_weak_clone).await;
+        });
+    });
+
+    // IMPORT / EXPORT LOGIC
+    // the file that was previewed, applied once the user picks how
+    let pending_import: Arc<Mutex<Option<ImportPreview>>> = Arc::new(Mutex::new(None));
+
+    let ui_weak_for_export = ui_weak.clone();
+    let last_fav_for_export = last_fav.clone();
+    main_window.on_export_favorites(move |path, format| {
+        let ui_weak_clone = ui_weak_for_export.clone();
+        let favorites = last_fav_for_export.clone();
+        tokio::spawn(async move {
+            let Some(path) = transfer_path(&path) else {
+                show_error_toast(ui_weak_clone, "Type a file to export to".to_string());
+                return;
+            };
+            let favorites = favorites.lock().await;
+            let format = ExchangeFormat::from_label(&format);
+            match favorites::export_file(&path, favorites.favorites(), format) {
+                Ok(()) => show_transfer_status(
+                    ui_weak_clone,
+                    format!(
+                        "Exported {} favorites to {}",
+                        favorites.favorites().len(),
+                        path.display()
+                    ),
+                    Vec::new(),
+                ),
+                Err(e) => {
+                    eprintln!("Failed to export favorites: {e}");
+                    show_error_toast(ui_weak_clone, format!("Export failed: {e}"));
+                }
+            }
+        });
+    });
+
+    let ui_weak_for_preview = ui_weak.clone();
+    let last_fav_for_preview = last_fav.clone();
+    let pending_import_for_preview = pending_import.clone();
+    main_window.on_preview_favorites_import(move |path, format| {
+        let ui_weak_clone = ui_weak_for_preview.clone();
+        let favorites = last_fav_for_preview.clone();
+        let pending = pending_import_for_preview.clone();
+        tokio::spawn(async move {
+            let Some(path) = transfer_path(&path) else {
+                show_error_toast(ui_weak_clone, "Type a file to import from".to_string());
+                return;
+            };
+            let incoming = match favorites::import_file(&path, ExchangeFormat::from_label(&format))
+            {
+                Ok(incoming) => incoming,
+                Err(e) => {
+                    eprintln!("Failed to import favorites: {e}");
+                    show_error_toast(ui_weak_clone, format!("Import failed: {e}"));
                     return;
                 }
+            };
+
+            let favorites = favorites.lock().await;
+            let preview = favorites.preview_import(incoming);
+            let status = format!(
+                "{} new, {} already saved",
+                preview.new.len(),
+                preview.duplicates.len()
+            );
+            let items = import_items(&preview, favorites.favorites());
+            *pending.lock().await = Some(preview);
+            show_transfer_status(ui_weak_clone, status, items);
+        });
+    });
 
-                location = capitalize_first(location);
-
-                let ui_weak_clone = ui_weak_for_search.clone();
-                let forecast_clone = last_forecast_for_search.clone();
-                let fav_clone = last_fav_for_search.clone();
-
-                tokio::spawn(async move {
-                    match display_weather_info(location.clone(), ui_weak_clone.clone()).await {
-                        Ok(forecast) => {
-                            let lat = forecast.latitude;
-                            let lon = forecast.longitude;
-                            *forecast_clone.lock().await = Some(forecast);
-
-                            let favorites = fav_clone.lock().await;
-                            let is_fav = is_location_favorited(&favorites, lat, lon);
-
-                            if let Err(e) = ui_weak_clone.upgrade_in_event_loop(move |ui| {
-                                ui.set_selected_day_index(0);
-                                ui.set_is_favorited(is_fav);
-                            }) {
-                                eprintln!("Failed to update display after search: {e}");
-                            }
-                        }
-                        Err(e) => {
-                            show_error_toast(
-                                ui_weak_clone.clone(),
-                                "Searched location does not exist!".to_string(),
-                            );
-                            eprintln!("Error at search: {e}");
-                        }
+    let ui_weak_for_apply = ui_weak.clone();
+    let last_fav_for_apply = last_fav.clone();
+    let pending_import_for_apply = pending_import.clone();
+    main_window.on_apply_favorites_import(move |update_existing| {
+        let ui_weak_clone = ui_weak_for_apply.clone();
+        let favorites = last_fav_for_apply.clone();
+        let pending = pending_import_for_apply.clone();
+        tokio::spawn(async move {
+            let Some(preview) = pending.lock().await.take() else {
+                return;
+            };
+            let result = favorites.lock().await.import(preview, update_existing);
+            match result {
+                Ok(changed) => {
+                    show_transfer_status(
+                        ui_weak_clone.clone(),
+                        format!("Imported, {changed} favorites added or updated"),
+                        Vec::new(),
+                    );
+                    // the new ones have no weather yet
+                    if let Err(e) =
+                        ui_weak_clone.upgrade_in_event_loop(|ui| ui.invoke_invoke_favorites_api())
+                    {
+                        eprintln!("Failed to reload favorites: {e}");
                     }
-                });
-            });
+                }
+                Err(e) => {
+                    eprintln!("Failed to save favorites: {e}");
+                    show_error_toast(ui_weak_clone, format!("Favorites couldn't be saved: {e}"));
+                }
+            }
+        });
+    });
 
-            // FAVORITE LOGIC
-            main_window.on_toggle_fav(move |_location_name, fav| {
-                let fav_clone = last_fav_for_fav.clone();
-                let forecast_clone = last_forecast_for_fav.clone();
-
-                tokio::spawn(async move {
-                    if let Some(forecast) = forecast_clone.lock().await.as_ref() {
-                        let lat = forecast.latitude;
-                        let lon = forecast.longitude;
-                        let address = forecast.resolved_address.clone();
-
-                        if fav {
-                            let location = FavoriteLocation::new(address, lat, lon);
-                            add_to_favorites(location);
-                        } else {
-                            remove_from_favorites(lat, lon);
-                        }
-
-                        *fav_clone.lock().await = read_favorites_from_file();
-                    }
-                });
-            });
+    let ui_weak_for_cancel = ui_weak.clone();
+    main_window.on_cancel_favorites_import(move || {
+        let ui_weak_clone = ui_weak_for_cancel.clone();
+        let pending = pending_import.clone();
+        tokio::spawn(async move {
+            *pending.lock().await = None;
+            show_transfer_status(ui_weak_clone, String::new(), Vec::new());
+        });
+    });
 
-            // FAVORITE PANEL LOGIC
-            let ui_weak_for_fav_panel = ui_weak.clone();
-            main_window.on_invoke_favorites_api(move || {
-                let fav_clone = last_fav_for_fav_panel.clone();
-                let ui_weak_clone = ui_weak_for_fav_panel.clone();
-
-                tokio::spawn(async move {
-                    let favorites_list = fav_clone.lock().await.clone();
-
-                    let mut tasks = Vec::new();
-                    for fav_location in favorites_list {
-                        let lat = fav_location.lat;
-                        let lon = fav_location.lon;
-                        let saved_name = fav_location.address.clone();
-                        tasks.push(tokio::spawn(async move {
-                            match get_fav_city_info_by_coords_with_retry(lat, lon, 3).await {
-                                Ok(data) => Some((data, saved_name)),
-                                Err(e) => {
-                                    eprintln!("Error getting location ({}, {}): {}", lat, lon, e);
-                                    None
-                                }
-                            }
-                        }));
-                    }
+    // UNITS LOGIC
+    let ui_weak_for_units = ui_weak.clone();
+    main_window.on_units_changed(move |label| {
+        let Some(system) = UnitSystem::from_label(&label) else {
+            eprintln!("Unknown unit system '{label}'");
+            return;
+        };
+        let new_units = Units::new(system, unit_overrides);
+
+        let selected_day = match ui_weak_for_units.upgrade() {
+            Some(ui) => ui.get_selected_day_index() as usize,
+            None => 0,
+        };
+
+        let ui_weak_clone = ui_weak_for_units.clone();
+        let units_clone = units_for_units.clone();
+        let forecast_clone = last_forecast_for_units.clone();
+        let fav_weather_clone = last_fav_weather_for_units.clone();
+        let fav_clone = last_fav_for_units.clone();
+        let rules_clone = rules_for_units.clone();
+        let matches_clone = rule_matches_for_units.clone();
+
+        tokio::spawn(async move {
+            *units_clone.lock().await = new_units;
+
+            if let Some(forecast) = forecast_clone.lock().await.as_ref() {
+                update_days_list(ui_weak_clone.clone(), forecast, new_units);
+                if let Some(day) = forecast.days.get(selected_day) {
+                    update_day_display(ui_weak_clone.clone(), day, new_units);
+                }
+            }
 
-                    let mut results = Vec::new();
-                    for task in tasks {
-                        match task.await {
-                            Ok(Some(data)) => {
-                                results.push(data);
-                            }
-                            Ok(None) => {
-                                eprintln!("Error getting location");
-                            }
-                            Err(e) => {
-                                eprintln!("Task join error: {}", e);
-                            }
-                        }
-                    }
+            refresh_favorites_panel(
+                &fav_clone,
+                &fav_weather_clone,
+                &units_clone,
+                ui_weak_clone.clone(),
+            )
+            .await;
+
+            update_rules_list(
+                ui_weak_clone.clone(),
+                &rules_clone.lock().await,
+                fav_clone.lock().await.favorites(),
+                new_units,
+            );
+            update_rule_matches(ui_weak_clone, &matches_clone.lock().await, new_units);
+        });
+    });
 
-                    let favorite_locations: Vec<FavoritedLocation> = results
-                        .iter()
-                        .map(|(resp, saved_name)| FavoritedLocation {
-                            icon: SharedString::from(resp.days[0].icon.clone()),
-                            name: SharedString::from(saved_name.clone()),
-                            temp: resp.days[0].temp,
-                            tempmin: resp.days[0].tempmin,
-                            tempmax: resp.days[0].tempmax,
-                        })
-                        .collect();
-
-                    if let Err(e) = ui_weak_clone.upgrade_in_event_loop(move |ui| {
-                        let favorites_model =
-                            std::rc::Rc::new(slint::VecModel::from(favorite_locations));
-                        ui.set_favorites(favorites_model.into());
-                    }) {
-                        eprintln!("Failed to update favorites panel: {e}");
+    // RULES LOGIC
+    let ui_weak_for_rules_opened = ui_weak.clone();
+    let rules_for_opened = rules.clone();
+    let units_for_rules_opened = units_for_rules.clone();
+    main_window.on_rules_opened(move || {
+        let ui_weak_clone = ui_weak_for_rules_opened.clone();
+        let fav_clone = last_fav_for_rules.clone();
+        let rules_clone = rules_for_opened.clone();
+        let units_clone = units_for_rules_opened.clone();
+
+        tokio::spawn(async move {
+            // favorites may have been renamed since the list was built
+            let favorites = fav_clone.lock().await.favorites().to_vec();
+            let units = *units_clone.lock().await;
+            update_rule_locations(ui_weak_clone.clone(), &favorites);
+            update_rules_list(ui_weak_clone, &rules_clone.lock().await, &favorites, units);
+        });
+    });
+
+    let ui_weak_for_add_rule = ui_weak.clone();
+    let rules_for_add = rules.clone();
+    let last_fav_for_add_rule = last_fav.clone();
+    let config_for_add_rule = rules_config.clone();
+    let units_for_add_rule = units_for_rules.clone();
+    let check_for_add = check_rules_now.clone();
+    main_window.on_add_rule(
+        move |metric, comparison, threshold, day, from, to, location| {
+            let ui_weak_clone = ui_weak_for_add_rule.clone();
+            let rules_clone = rules_for_add.clone();
+            let fav_clone = last_fav_for_add_rule.clone();
+            let config_clone = config_for_add_rule.clone();
+            let units_clone = units_for_add_rule.clone();
+            let check_clone = check_for_add.clone();
+
+            tokio::spawn(async move {
+                let units = *units_clone.lock().await;
+                let rule = match parse_rule(
+                    &metric,
+                    &comparison,
+                    &threshold,
+                    &day,
+                    &from,
+                    &to,
+                    &location,
+                    &units,
+                ) {
+                    Ok(rule) => rule,
+                    Err(message) => {
+                        show_error_toast(ui_weak_clone, message);
+                        return;
                     }
-                });
+                };
+
+                let favorites = fav_clone.lock().await.favorites().to_vec();
+                let mut rules = rules_clone.lock().await;
+                rules.push(rule);
+                save_rules(ui_weak_clone.clone(), &config_clone, &rules);
+                update_rules_list(ui_weak_clone, &rules, &favorites, units);
+                check_clone.notify_one();
             });
+        },
+    );
 
-            if let Err(e) = main_window.run() {
-                eprintln!("Failed to run window {e}")
+    let ui_weak_for_remove_rule = ui_weak.clone();
+    let rules_for_remove = rules.clone();
+    let last_fav_for_remove_rule = last_fav.clone();
+    let config_for_remove_rule = rules_config.clone();
+    let units_for_remove_rule = units_for_rules.clone();
+    let check_for_remove = check_rules_now.clone();
+    main_window.on_remove_rule(move |index| {
+        let ui_weak_clone = ui_weak_for_remove_rule.clone();
+        let rules_clone = rules_for_remove.clone();
+        let fav_clone = last_fav_for_remove_rule.clone();
+        let config_clone = config_for_remove_rule.clone();
+        let units_clone = units_for_remove_rule.clone();
+        let check_clone = check_for_remove.clone();
+
+        tokio::spawn(async move {
+            let units = *units_clone.lock().await;
+            let favorites = fav_clone.lock().await.favorites().to_vec();
+            let mut rules = rules_clone.lock().await;
+            if (index as usize) < rules.len() {
+                rules.remove(index as usize);
+                save_rules(ui_weak_clone.clone(), &config_clone, &rules);
             }
-        }
-        Err(e) => {
-            eprintln!("EROARE: {}", e);
-        }
+            update_rules_list(ui_weak_clone, &rules, &favorites, units);
+            check_clone.notify_one();
+        });
+    });
+
+    let ui_weak_for_toggle_rule = ui_weak.clone();
+    let rules_for_toggle = rules.clone();
+    let last_fav_for_toggle_rule = last_fav.clone();
+    let check_for_toggle = check_rules_now.clone();
+    main_window.on_toggle_rule(move |index, enabled| {
+        let ui_weak_clone = ui_weak_for_toggle_rule.clone();
+        let rules_clone = rules_for_toggle.clone();
+        let fav_clone = last_fav_for_toggle_rule.clone();
+        let config_clone = rules_config.clone();
+        let units_clone = units_for_rules.clone();
+        let check_clone = check_for_toggle.clone();
+
+        tokio::spawn(async move {
+            let units = *units_clone.lock().await;
+            let favorites = fav_clone.lock().await.favorites().to_vec();
+            let mut rules = rules_clone.lock().await;
+            if let Some(rule) = rules.get_mut(index as usize) {
+                rule.enabled = enabled;
+                save_rules(ui_weak_clone.clone(), &config_clone, &rules);
+            }
+            update_rules_list(ui_weak_clone, &rules, &favorites, units);
+            check_clone.notify_one();
+        });
+    });
+
+    main_window.on_check_rules(move || {
+        check_rules_now.notify_one();
+    });
+
+    if let Err(e) = main_window.run() {
+        eprintln!("Failed to run window {e}")
     }
 
     Ok(())
@@ -0,0 +1,17 @@
+use notify_rust::Notification;
+use weather_core::rules::NotificationSink;
+
+/// Freedesktop notifications over D-Bus
+pub struct DesktopNotifier;
+
+impl NotificationSink for DesktopNotifier {
+    fn notify(&self, summary: &str, body: &str) -> Result<(), anyhow::Error> {
+        Notification::new()
+            .appname("Weather")
+            .summary(summary)
+            .body(body)
+            .icon("weather-overcast")
+            .show()?;
+        Ok(())
+    }
+}
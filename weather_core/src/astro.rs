@@ -0,0 +1,258 @@
+use crate::weather_structs::ForecastResponse;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime};
+
+// Local sunrise/sunset and moon phase, for providers that don't send them.
+// Sun times follow the sunrise equation NOAA uses, good to a minute or two.
+
+const J2000: f64 = 2451545.0;
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+const SYNODIC_MONTH: f64 = 29.530588853;
+// 2000-01-06 18:14 UTC
+const KNOWN_NEW_MOON_JD: f64 = 2451550.26;
+
+pub enum SunTimes {
+    Normal {
+        sunrise: NaiveTime,
+        sunset: NaiveTime,
+    },
+    /// The sun never sets (polar day)
+    AlwaysUp,
+    /// The sun never rises (polar night)
+    AlwaysDown,
+}
+
+impl SunTimes {
+    pub fn day_length(&self) -> Duration {
+        match self {
+            SunTimes::Normal { sunrise, sunset } => *sunset - *sunrise,
+            SunTimes::AlwaysUp => Duration::hours(24),
+            SunTimes::AlwaysDown => Duration::zero(),
+        }
+    }
+}
+
+/// Sunrise and sunset on `date` at the given point, in local time `tz_offset` hours from UTC
+pub fn sun_times(date: NaiveDate, lat: f64, lon: f64, tz_offset: f64) -> SunTimes {
+    let n = days_since_j2000(date);
+    let mean_noon = n - lon / 360.0;
+
+    let anomaly = (357.5291 + 0.98560028 * mean_noon).rem_euclid(360.0);
+    let m = anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.0200 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_lon = (anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+
+    let transit = J2000 + mean_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * ecliptic_lon).sin();
+    let declination = (ecliptic_lon.sin() * 23.4397_f64.to_radians().sin()).asin();
+
+    // -0.833° accounts for refraction and the size of the sun's disc
+    let lat = lat.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - lat.sin() * declination.sin())
+        / (lat.cos() * declination.cos());
+    if cos_hour_angle < -1.0 {
+        return SunTimes::AlwaysUp;
+    }
+    if cos_hour_angle > 1.0 {
+        return SunTimes::AlwaysDown;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    SunTimes::Normal {
+        sunrise: local_time(transit - hour_angle / 360.0, tz_offset),
+        sunset: local_time(transit + hour_angle / 360.0, tz_offset),
+    }
+}
+
+/// 0 is a new moon, 0.25 first quarter, 0.5 full moon, 0.75 last quarter
+pub fn moon_phase(date: NaiveDate) -> f32 {
+    let jd = J2000 + days_since_j2000(date);
+    ((jd - KNOWN_NEW_MOON_JD) / SYNODIC_MONTH).rem_euclid(1.0) as f32
+}
+
+const MOON_PHASES: [(&str, &str); 8] = [
+    ("New Moon", "🌑"),
+    ("Waxing Crescent", "🌒"),
+    ("First Quarter", "🌓"),
+    ("Waxing Gibbous", "🌔"),
+    ("Full Moon", "🌕"),
+    ("Waning Gibbous", "🌖"),
+    ("Last Quarter", "🌗"),
+    ("Waning Crescent", "🌘"),
+];
+
+fn moon_phase_entry(phase: f32) -> (&'static str, &'static str) {
+    MOON_PHASES[(phase.rem_euclid(1.0) * 8.0).round() as usize % MOON_PHASES.len()]
+}
+
+pub fn moon_phase_name(phase: f32) -> &'static str {
+    moon_phase_entry(phase).0
+}
+
+pub fn moon_phase_icon(phase: f32) -> &'static str {
+    moon_phase_entry(phase).1
+}
+
+// "HH:MM:SS" sunrise/sunset strings, as providers send them
+fn day_length(sunrise: &str, sunset: &str) -> Option<Duration> {
+    let sunrise = NaiveTime::parse_from_str(sunrise, "%H:%M:%S").ok()?;
+    let sunset = NaiveTime::parse_from_str(sunset, "%H:%M:%S").ok()?;
+    Some(sunset - sunrise)
+}
+
+/// Fills in whatever sun and moon data the provider left out, and the daylight change
+/// versus the day before. `tz_offset` is the location's offset from UTC in hours.
+pub fn fill_missing(forecast: &mut ForecastResponse, tz_offset: f64) {
+    let (lat, lon) = (forecast.latitude, forecast.longitude);
+
+    for day in &mut forecast.days {
+        let Ok(date) = NaiveDate::parse_from_str(&day.datetime, "%Y-%m-%d") else {
+            continue;
+        };
+
+        let today = sun_times(date, lat, lon, tz_offset);
+        if day.sunrise.is_none()
+            && day.sunset.is_none()
+            && let SunTimes::Normal { sunrise, sunset } = &today
+        {
+            day.sunrise = Some(sunrise.format("%H:%M:%S").to_string());
+            day.sunset = Some(sunset.format("%H:%M:%S").to_string());
+        }
+
+        if day.daylight.is_none() {
+            let length = match (&day.sunrise, &day.sunset) {
+                (Some(sunrise), Some(sunset)) => day_length(sunrise, sunset),
+                _ => None,
+            };
+            day.daylight = Some(length.unwrap_or_else(|| today.day_length()).num_seconds());
+        }
+
+        if day.moon_phase.is_none() {
+            day.moon_phase = Some(moon_phase(date));
+        }
+
+        // both days computed the same way, so the error mostly cancels out
+        if let Some(yesterday) = date.pred_opt() {
+            let yesterday = sun_times(yesterday, lat, lon, tz_offset);
+            day.daylight_change = Some((today.day_length() - yesterday.day_length()).num_seconds());
+        }
+    }
+}
+
+fn days_since_j2000(date: NaiveDate) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+    (date - epoch).num_days() as f64
+}
+
+fn local_time(julian_day: f64, tz_offset: f64) -> NaiveTime {
+    let unix_seconds = ((julian_day - UNIX_EPOCH_JD) * 86400.0 + tz_offset * 3600.0).round();
+    DateTime::from_timestamp(unix_seconds as i64, 0)
+        .map(|time| time.time())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather_structs::ForecastDay;
+    use chrono::Utc;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    fn within_minutes(actual: NaiveTime, expected: &str, minutes: i64) -> bool {
+        (actual - time(expected)).num_minutes().abs() <= minutes
+    }
+
+    fn forecast(latitude: f64, longitude: f64, datetime: &str) -> ForecastResponse {
+        ForecastResponse {
+            resolved_address: String::new(),
+            latitude,
+            longitude,
+            days: vec![ForecastDay {
+                datetime: datetime.to_string(),
+                temp_max: 0.0,
+                temp_min: 0.0,
+                temp: 0.0,
+                feels_like: 0.0,
+                humidity: 0.0,
+                precip: 0.0,
+                precip_prob: 0.0,
+                snow: 0.0,
+                snow_depth: 0.0,
+                wind_speed: 0.0,
+                wind_dir: 0.0,
+                aqieur: None,
+                severe_risk: None,
+                sunrise: None,
+                sunset: None,
+                daylight: None,
+                daylight_change: None,
+                moon_phase: None,
+                conditions: String::new(),
+                icon: String::new(),
+                hours: Vec::new(),
+            }],
+            alerts: Vec::new(),
+            fetched_at: Utc::now(),
+            stale: false,
+        }
+    }
+
+    #[test]
+    fn sun_times_match_published_times_for_iasi() {
+        // Iași on the June solstice, EEST: sunrise 05:14, sunset 21:09 (timeanddate.com)
+        let SunTimes::Normal { sunrise, sunset } = sun_times(date("2024-06-21"), 47.16, 27.59, 3.0)
+        else {
+            panic!("the sun rises and sets in Iași");
+        };
+        assert!(within_minutes(sunrise, "05:14", 3), "sunrise at {sunrise}");
+        assert!(within_minutes(sunset, "21:09", 3), "sunset at {sunset}");
+    }
+
+    #[test]
+    fn polar_day_and_night_have_no_sunrise() {
+        // Tromsø
+        let mut summer = forecast(69.65, 18.96, "2024-06-21");
+        fill_missing(&mut summer, 2.0);
+        let day = &summer.days[0];
+        assert_eq!((&day.sunrise, &day.sunset), (&None, &None));
+        assert_eq!(day.daylight, Some(24 * 3600));
+
+        let mut winter = forecast(69.65, 18.96, "2024-12-21");
+        fill_missing(&mut winter, 1.0);
+        let day = &winter.days[0];
+        assert_eq!((&day.sunrise, &day.sunset), (&None, &None));
+        assert_eq!(day.daylight, Some(0));
+    }
+
+    #[test]
+    fn fill_missing_keeps_what_the_provider_sent() {
+        let mut response = forecast(47.16, 27.59, "2024-06-21");
+        response.days[0].sunrise = Some("05:00:00".to_string());
+        response.days[0].sunset = Some("21:00:00".to_string());
+        fill_missing(&mut response, 3.0);
+
+        let day = &response.days[0];
+        assert_eq!(day.sunrise.as_deref(), Some("05:00:00"));
+        assert_eq!(day.daylight, Some(16 * 3600));
+        assert!(day.moon_phase.is_some());
+    }
+
+    #[test]
+    fn moon_phase_of_known_full_and_new_moons() {
+        // full moon 2024-01-25 17:54 UTC, new moon 2024-02-09 22:59 UTC
+        let full = moon_phase(date("2024-01-25"));
+        assert!((full - 0.5).abs() < 0.03, "phase {full}");
+        assert_eq!(moon_phase_name(full), "Full Moon");
+
+        let new = moon_phase(date("2024-02-09"));
+        assert!(!(0.03..=0.97).contains(&new), "phase {new}");
+        assert_eq!(moon_phase_name(new), "New Moon");
+    }
+}
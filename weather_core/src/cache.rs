@@ -0,0 +1,268 @@
+use crate::config::Config;
+use crate::error::WeatherError;
+use crate::paths;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Raw provider responses on disk, one JSON file per request
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    offline: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    fetched_at: DateTime<Utc>,
+    body: String,
+}
+
+pub struct CachedResponse {
+    pub body: String,
+    pub fetched_at: DateTime<Utc>,
+    /// Older than the TTL, served because the network wasn't reachable
+    pub stale: bool,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration, offline: bool) -> Self {
+        Self { dir, ttl, offline }
+    }
+
+    /// The cache under $XDG_CACHE_HOME/rust-weather-app, None when there's no cache directory
+    pub fn from_config(config: &Config) -> Option<Arc<Self>> {
+        paths::cache_dir().map(|dir| {
+            Arc::new(Self::new(
+                dir.join("responses"),
+                Duration::from_secs(config.cache_ttl_minutes * 60),
+                config.offline,
+            ))
+        })
+    }
+
+    /// Returns the cached body for `key` while it's fresh, otherwise calls `fetch` and stores
+    /// the result. If `fetch` fails with a transient error (or we're in offline mode) an expired
+    /// entry is still returned, flagged as stale, so the app keeps working without a connection.
+    /// Errors like a rejected API key are passed through instead of being hidden by old data.
+    pub async fn get_or_fetch<F, Fut>(
+        &self,
+        key: &str,
+        fetch: F,
+    ) -> Result<CachedResponse, WeatherError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String, WeatherError>>,
+    {
+        let cached = self.read(key);
+
+        if let Some(entry) = &cached
+            && self.is_fresh(entry)
+        {
+            return Ok(CachedResponse {
+                body: entry.body.clone(),
+                fetched_at: entry.fetched_at,
+                stale: false,
+            });
+        }
+
+        let result = if self.offline {
+            Err(WeatherError::Offline)
+        } else {
+            fetch().await
+        };
+
+        match (result, cached) {
+            (Ok(body), _) => {
+                let entry = CacheEntry {
+                    key: key.to_string(),
+                    fetched_at: Utc::now(),
+                    body,
+                };
+                self.write(&entry);
+
+                Ok(CachedResponse {
+                    body: entry.body,
+                    fetched_at: entry.fetched_at,
+                    stale: false,
+                })
+            }
+            (Err(e), Some(entry)) if self.offline || e.is_transient() => {
+                eprintln!(
+                    "Serving cached response from {} for {key}: {e}",
+                    entry.fetched_at
+                );
+                Ok(CachedResponse {
+                    body: entry.body,
+                    fetched_at: entry.fetched_at,
+                    stale: true,
+                })
+            }
+            (Err(e), _) => Err(e),
+        }
+    }
+
+    fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        let age = Utc::now().signed_duration_since(entry.fetched_at);
+        age.to_std().is_ok_and(|age| age < self.ttl)
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(key)))
+    }
+
+    fn read(&self, key: &str) -> Option<CacheEntry> {
+        let content = fs::read_to_string(self.path_for(key)).ok()?;
+        match serde_json::from_str::<CacheEntry>(&content) {
+            // two keys hashing to the same file is unlikely, but not impossible
+            Ok(entry) if entry.key == key => Some(entry),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("Ignoring unreadable cache entry for {key}: {e}");
+                None
+            }
+        }
+    }
+
+    fn write(&self, entry: &CacheEntry) {
+        if let Err(e) = fs::create_dir_all(&self.dir) {
+            eprintln!("Failed to create cache dir {}: {e}", self.dir.display());
+            return;
+        }
+
+        match serde_json::to_string(entry) {
+            Ok(json) => {
+                // a half-written entry would look corrupt and be thrown away
+                if let Err(e) = paths::write_atomically(&self.path_for(&entry.key), json.as_bytes())
+                {
+                    eprintln!("Failed to write cache entry for {}: {e}", entry.key);
+                }
+            }
+            Err(e) => eprintln!("Error serializing cache entry for {}: {e}", entry.key),
+        }
+    }
+}
+
+// stable across Rust versions, unlike DefaultHasher, so old cache files stay valid
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn cache(dir: &std::path::Path, ttl: Duration, offline: bool) -> ResponseCache {
+        ResponseCache::new(dir.join("responses"), ttl, offline)
+    }
+
+    async fn get(
+        cache: &ResponseCache,
+        calls: &Cell<u32>,
+        result: Result<&str, WeatherError>,
+    ) -> Result<CachedResponse, WeatherError> {
+        cache
+            .get_or_fetch("forecast Iasi", || async {
+                calls.set(calls.get() + 1);
+                result.map(str::to_string)
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn fresh_entries_are_served_without_fetching() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), Duration::from_secs(600), false);
+        let calls = Cell::new(0);
+
+        let first = get(&cache, &calls, Ok("first")).await.unwrap();
+        let second = get(&cache, &calls, Ok("second")).await.unwrap();
+
+        assert_eq!(calls.get(), 1);
+        assert_eq!((first.body.as_str(), first.stale), ("first", false));
+        assert_eq!((second.body.as_str(), second.stale), ("first", false));
+        assert_eq!(second.fetched_at, first.fetched_at);
+
+        // only the entry itself is left, no temporary file next to it
+        let files: Vec<_> = fs::read_dir(dir.path().join("responses"))
+            .unwrap()
+            .collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_fetched_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), Duration::ZERO, false);
+        let calls = Cell::new(0);
+
+        get(&cache, &calls, Ok("first")).await.unwrap();
+        let second = get(&cache, &calls, Ok("second")).await.unwrap();
+
+        assert_eq!(calls.get(), 2);
+        assert_eq!((second.body.as_str(), second.stale), ("second", false));
+    }
+
+    #[tokio::test]
+    async fn expired_entries_cover_transient_errors_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), Duration::ZERO, false);
+        let calls = Cell::new(0);
+
+        let fetched = get(&cache, &calls, Ok("cached")).await.unwrap();
+        for error in [
+            WeatherError::Timeout,
+            WeatherError::Network("connection refused".to_string()),
+            WeatherError::ProviderOutage(503),
+        ] {
+            let stale = get(&cache, &calls, Err(error.clone())).await;
+            let stale = stale.unwrap_or_else(|e| panic!("{error}: {e}"));
+            assert_eq!((stale.body.as_str(), stale.stale), ("cached", true));
+            assert_eq!(stale.fetched_at, fetched.fetched_at);
+        }
+
+        let rejected = get(&cache, &calls, Err(WeatherError::Auth(401))).await;
+        assert!(matches!(rejected, Err(WeatherError::Auth(401))));
+    }
+
+    #[tokio::test]
+    async fn transient_errors_without_an_entry_are_passed_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), Duration::from_secs(600), false);
+        let calls = Cell::new(0);
+
+        let result = get(&cache, &calls, Err(WeatherError::Timeout)).await;
+        assert!(matches!(result, Err(WeatherError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn offline_mode_serves_old_entries_without_fetching() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = Cell::new(0);
+        get(
+            &cache(dir.path(), Duration::ZERO, false),
+            &calls,
+            Ok("cached"),
+        )
+        .await
+        .unwrap();
+
+        let offline = cache(dir.path(), Duration::ZERO, true);
+        let stale = get(&offline, &calls, Ok("fresh")).await.unwrap();
+        assert_eq!(calls.get(), 1);
+        assert_eq!((stale.body.as_str(), stale.stale), ("cached", true));
+
+        let empty = tempfile::tempdir().unwrap();
+        let offline = cache(empty.path(), Duration::ZERO, true);
+        let result = get(&offline, &calls, Ok("fresh")).await;
+        assert!(matches!(result, Err(WeatherError::Offline)));
+    }
+}
@@ -0,0 +1,460 @@
+use crate::paths;
+use crate::units::{LengthUnit, SpeedUnit, TempUnit, UnitOverrides, UnitSystem, Units};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ENV_PREFIX: &str = "WEATHER_APP_";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProviderKind {
+    VisualCrossing,
+    OpenMeteo,
+}
+
+impl std::str::FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "visual-crossing" => Ok(ProviderKind::VisualCrossing),
+            "open-meteo" => Ok(ProviderKind::OpenMeteo),
+            _ => Err(anyhow::anyhow!(
+                "Unknown provider '{s}' (expected visual-crossing or open-meteo)"
+            )),
+        }
+    }
+}
+
+/// Final settings the app runs with, after every layer has been merged
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub provider: ProviderKind,
+    /// Falls back to Open-Meteo when the main provider fails
+    pub fallback: bool,
+    pub api_key: Option<String>,
+    /// Overrides the Open-Meteo hosts, e.g. for a local server replaying recorded responses
+    pub open_meteo_url: Option<String>,
+
+    pub default_location: String,
+    pub units: UnitSystem,
+    pub unit_overrides: UnitOverrides,
+    pub lang: String,
+    /// How many times a request that failed for a passing reason is tried again
+    pub max_retries: u32,
+    /// Seconds a request may take with all its retries before giving up
+    pub retry_deadline_secs: u64,
+
+    /// How long a cached response is used before asking the provider again
+    pub cache_ttl_minutes: u64,
+    /// Never touch the network, only answer from the cache
+    pub offline: bool,
+
+    /// How often notification rules are checked against the favorites, 0 turns it off
+    pub rules_check_minutes: u64,
+    /// How often the shown forecast and the favorites are fetched again, 0 turns it off
+    pub refresh_minutes: u64,
+
+    /// GeoNames city dump for offline search and naming, see `CityIndex`
+    pub cities_file: Option<PathBuf>,
+
+    /// How many recent searches are remembered, 0 turns the history off
+    pub history_size: usize,
+
+    /// Where favorites, search history and the city list live instead of the XDG data dir
+    pub data_dir: Option<PathBuf>,
+
+    /// Seconds to wait for a connection, and for each read once connected
+    pub connect_timeout_secs: u64,
+    pub read_timeout_secs: u64,
+    /// http://, https://, socks5:// or socks5h:// proxy for every request. Without it the
+    /// usual HTTPS_PROXY / ALL_PROXY / NO_PROXY variables are used.
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates, e.g. a company's TLS inspection CA
+    pub ca_file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            provider: ProviderKind::VisualCrossing,
+            fallback: true,
+            api_key: None,
+            open_meteo_url: None,
+            default_location: "Iasi".to_string(),
+            units: UnitSystem::Metric,
+            unit_overrides: UnitOverrides::default(),
+            lang: "en".to_string(),
+            max_retries: 3,
+            retry_deadline_secs: 20,
+            cache_ttl_minutes: 30,
+            offline: false,
+            rules_check_minutes: 60,
+            refresh_minutes: 30,
+            cities_file: None,
+            history_size: 10,
+            data_dir: None,
+            connect_timeout_secs: 10,
+            read_timeout_secs: 30,
+            proxy: None,
+            ca_file: None,
+        }
+    }
+}
+
+/// One source of settings (file, environment, command line). Unset fields leave the
+/// value from the layer below untouched.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub provider: Option<ProviderKind>,
+    pub fallback: Option<bool>,
+    pub api_key: Option<String>,
+    pub open_meteo_url: Option<String>,
+
+    pub default_location: Option<String>,
+    pub units: Option<UnitSystem>,
+    pub temperature_unit: Option<TempUnit>,
+    pub wind_unit: Option<SpeedUnit>,
+    pub precip_unit: Option<LengthUnit>,
+    pub snow_unit: Option<LengthUnit>,
+    pub lang: Option<String>,
+    pub max_retries: Option<u32>,
+    pub retry_deadline_secs: Option<u64>,
+
+    pub cache_ttl_minutes: Option<u64>,
+    pub offline: Option<bool>,
+
+    pub rules_check_minutes: Option<u64>,
+    pub refresh_minutes: Option<u64>,
+
+    pub cities_file: Option<PathBuf>,
+
+    pub history_size: Option<usize>,
+
+    pub data_dir: Option<PathBuf>,
+
+    pub connect_timeout_secs: Option<u64>,
+    pub read_timeout_secs: Option<u64>,
+    pub proxy: Option<String>,
+    pub ca_file: Option<PathBuf>,
+}
+
+impl ConfigLayer {
+    pub fn from_file(path: &Path) -> Result<Self, anyhow::Error> {
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid config file {}: {e}", path.display()))
+    }
+
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// The `WEATHER_APP_*` settings, looked up by their full name through `lookup`
+    fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, anyhow::Error> {
+        let var = |name: &str| lookup(&format!("{ENV_PREFIX}{name}"));
+
+        Ok(ConfigLayer {
+            provider: var("PROVIDER").map(|p| p.parse()).transpose()?,
+            fallback: var("FALLBACK")
+                .map(|f| f.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}FALLBACK: {e}"))?,
+            api_key: var("API_KEY"),
+            open_meteo_url: var("OPEN_METEO_URL"),
+            default_location: var("DEFAULT_LOCATION"),
+            units: var("UNITS").map(|u| u.parse()).transpose()?,
+            temperature_unit: var("TEMPERATURE_UNIT").map(|u| u.parse()).transpose()?,
+            wind_unit: var("WIND_UNIT").map(|u| u.parse()).transpose()?,
+            precip_unit: var("PRECIP_UNIT").map(|u| u.parse()).transpose()?,
+            snow_unit: var("SNOW_UNIT").map(|u| u.parse()).transpose()?,
+            lang: var("LANG"),
+            max_retries: var("MAX_RETRIES")
+                .map(|r| r.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}MAX_RETRIES: {e}"))?,
+            retry_deadline_secs: var("RETRY_DEADLINE_SECS")
+                .map(|t| t.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}RETRY_DEADLINE_SECS: {e}"))?,
+            cache_ttl_minutes: var("CACHE_TTL_MINUTES")
+                .map(|t| t.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}CACHE_TTL_MINUTES: {e}"))?,
+            offline: var("OFFLINE")
+                .map(|o| o.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}OFFLINE: {e}"))?,
+            rules_check_minutes: var("RULES_CHECK_MINUTES")
+                .map(|m| m.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}RULES_CHECK_MINUTES: {e}"))?,
+            refresh_minutes: var("REFRESH_MINUTES")
+                .map(|m| m.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}REFRESH_MINUTES: {e}"))?,
+            cities_file: var("CITIES_FILE").map(PathBuf::from),
+            history_size: var("HISTORY_SIZE")
+                .map(|n| n.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}HISTORY_SIZE: {e}"))?,
+            data_dir: var("DATA_DIR").map(PathBuf::from),
+            connect_timeout_secs: var("CONNECT_TIMEOUT_SECS")
+                .map(|t| t.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}CONNECT_TIMEOUT_SECS: {e}"))?,
+            read_timeout_secs: var("READ_TIMEOUT_SECS")
+                .map(|t| t.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("{ENV_PREFIX}READ_TIMEOUT_SECS: {e}"))?,
+            proxy: var("PROXY"),
+            ca_file: var("CA_FILE").map(PathBuf::from),
+        })
+    }
+
+    fn apply_to(self, config: &mut Config) {
+        if let Some(provider) = self.provider {
+            config.provider = provider;
+        }
+        if let Some(fallback) = self.fallback {
+            config.fallback = fallback;
+        }
+        if self.api_key.is_some() {
+            config.api_key = self.api_key;
+        }
+        if self.open_meteo_url.is_some() {
+            config.open_meteo_url = self.open_meteo_url;
+        }
+        if let Some(default_location) = self.default_location {
+            config.default_location = default_location;
+        }
+        if let Some(units) = self.units {
+            config.units = units;
+        }
+        if self.temperature_unit.is_some() {
+            config.unit_overrides.temperature = self.temperature_unit;
+        }
+        if self.wind_unit.is_some() {
+            config.unit_overrides.wind = self.wind_unit;
+        }
+        if self.precip_unit.is_some() {
+            config.unit_overrides.precip = self.precip_unit;
+        }
+        if self.snow_unit.is_some() {
+            config.unit_overrides.snow = self.snow_unit;
+        }
+        if let Some(lang) = self.lang {
+            config.lang = lang;
+        }
+        if let Some(max_retries) = self.max_retries {
+            config.max_retries = max_retries;
+        }
+        if let Some(retry_deadline_secs) = self.retry_deadline_secs {
+            config.retry_deadline_secs = retry_deadline_secs;
+        }
+        if let Some(cache_ttl_minutes) = self.cache_ttl_minutes {
+            config.cache_ttl_minutes = cache_ttl_minutes;
+        }
+        if let Some(offline) = self.offline {
+            config.offline = offline;
+        }
+        if let Some(rules_check_minutes) = self.rules_check_minutes {
+            config.rules_check_minutes = rules_check_minutes;
+        }
+        if let Some(refresh_minutes) = self.refresh_minutes {
+            config.refresh_minutes = refresh_minutes;
+        }
+        if self.cities_file.is_some() {
+            config.cities_file = self.cities_file;
+        }
+        if let Some(history_size) = self.history_size {
+            config.history_size = history_size;
+        }
+        if self.data_dir.is_some() {
+            config.data_dir = self.data_dir;
+        }
+        if let Some(connect_timeout_secs) = self.connect_timeout_secs {
+            config.connect_timeout_secs = connect_timeout_secs;
+        }
+        if let Some(read_timeout_secs) = self.read_timeout_secs {
+            config.read_timeout_secs = read_timeout_secs;
+        }
+        if self.proxy.is_some() {
+            config.proxy = self.proxy;
+        }
+        if self.ca_file.is_some() {
+            config.ca_file = self.ca_file;
+        }
+    }
+}
+
+impl Config {
+    pub fn display_units(&self) -> Units {
+        Units::new(self.units, self.unit_overrides)
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        paths::config_dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// `file_name` in `data_dir`, or in `$XDG_DATA_HOME/rust-weather-app` when that's unset
+    pub fn data_path(&self, file_name: &str) -> Option<PathBuf> {
+        self.data_dir
+            .clone()
+            .or_else(paths::data_dir)
+            .map(|dir| dir.join(file_name))
+    }
+
+    /// Defaults <- config file <- environment <- `overrides` (the command line)
+    ///
+    /// A missing file at the default location is fine, an explicitly requested one is not.
+    pub fn load(
+        explicit_path: Option<&Path>,
+        overrides: ConfigLayer,
+    ) -> Result<Config, anyhow::Error> {
+        Self::layered(explicit_path, ConfigLayer::from_env()?, overrides)
+    }
+
+    fn layered(
+        explicit_path: Option<&Path>,
+        env: ConfigLayer,
+        overrides: ConfigLayer,
+    ) -> Result<Config, anyhow::Error> {
+        let mut config = Config::default();
+
+        match explicit_path {
+            Some(path) => ConfigLayer::from_file(path)?.apply_to(&mut config),
+            None => {
+                if let Some(path) = Config::default_path()
+                    && path.exists()
+                {
+                    ConfigLayer::from_file(&path)?.apply_to(&mut config);
+                }
+            }
+        }
+
+        env.apply_to(&mut config);
+        overrides.apply_to(&mut config);
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env_layer(vars: &[(&str, &str)]) -> Result<ConfigLayer, anyhow::Error> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        ConfigLayer::from_vars(|name| vars.get(name).cloned())
+    }
+
+    fn config_file(dir: &Path, content: &str) -> PathBuf {
+        let path = dir.join("config.toml");
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = config_file(
+            dir.path(),
+            r#"
+                provider = "open-meteo"
+                default_location = "Cluj-Napoca"
+                units = "us"
+                max_retries = 5
+                cache_ttl_minutes = 10
+                lang = "ro"
+            "#,
+        );
+        let env = env_layer(&[
+            ("WEATHER_APP_DEFAULT_LOCATION", "Brasov"),
+            ("WEATHER_APP_MAX_RETRIES", "1"),
+            ("WEATHER_APP_TEMPERATURE_UNIT", "celsius"),
+            ("WEATHER_APP_OFFLINE", "true"),
+            // not one of ours
+            ("DEFAULT_LOCATION", "Paris"),
+        ])
+        .unwrap();
+        let cli = ConfigLayer {
+            default_location: Some("Suceava".to_string()),
+            offline: Some(false),
+            ..ConfigLayer::default()
+        };
+
+        let config = Config::layered(Some(&path), env, cli).unwrap();
+
+        // defaults, nobody set them
+        assert!(config.fallback);
+        assert_eq!(config.refresh_minutes, 30);
+        // the file
+        assert_eq!(config.provider, ProviderKind::OpenMeteo);
+        assert_eq!(config.units, UnitSystem::Us);
+        assert_eq!(config.cache_ttl_minutes, 10);
+        assert_eq!(config.lang, "ro");
+        // the environment over the file
+        assert_eq!(config.max_retries, 1);
+        assert_eq!(config.unit_overrides.temperature, Some(TempUnit::Celsius));
+        // the command line over everything
+        assert_eq!(config.default_location, "Suceava");
+        assert!(!config.offline);
+    }
+
+    #[test]
+    fn no_layers_means_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = config_file(dir.path(), "");
+        let config =
+            Config::layered(Some(&path), env_layer(&[]).unwrap(), ConfigLayer::default()).unwrap();
+        assert_eq!(config.default_location, Config::default().default_location);
+        assert_eq!(config.max_retries, Config::default().max_retries);
+    }
+
+    #[test]
+    fn invalid_values_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        for content in [
+            "max_retries = \"three\"",
+            "provider = \"weather-channel\"",
+            "refresh_minuts = 5",
+            "units = [",
+        ] {
+            let path = config_file(dir.path(), content);
+            let error =
+                Config::layered(Some(&path), ConfigLayer::default(), ConfigLayer::default())
+                    .expect_err(content)
+                    .to_string();
+            assert!(
+                error.starts_with("Invalid config file"),
+                "{content}: {error}"
+            );
+        }
+
+        let missing = dir.path().join("missing.toml");
+        assert!(
+            Config::layered(
+                Some(&missing),
+                ConfigLayer::default(),
+                ConfigLayer::default()
+            )
+            .is_err()
+        );
+
+        for (name, value) in [
+            ("WEATHER_APP_MAX_RETRIES", "three"),
+            ("WEATHER_APP_OFFLINE", "yes"),
+            ("WEATHER_APP_PROVIDER", "weather-channel"),
+        ] {
+            assert!(env_layer(&[(name, value)]).is_err(), "{name}={value}");
+        }
+    }
+}
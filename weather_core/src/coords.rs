@@ -0,0 +1,496 @@
+use thiserror::Error;
+
+// Coordinates the way people paste them: "47.16,27.58", 47°09'36"N 27°35'24"E,
+// plus codes like 8GR6+5X and geo:47.16,27.58 URIs.
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CoordsError {
+    /// Plain text like "Paris", to be searched for by name
+    #[error("not coordinates")]
+    NotCoordinates,
+
+    /// Looks like coordinates but can't be used; the message is meant for the user
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// Latitude and longitude in decimal degrees, checked to be on the globe
+pub fn parse_coords(input: &str) -> Result<(f64, f64), CoordsError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(CoordsError::NotCoordinates);
+    }
+
+    let geo_uri = input
+        .get(..4)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("geo:"))
+        .map(|_| &input[4..]);
+    let (lat, lon) = if let Some(rest) = geo_uri {
+        parse_geo_uri(rest)?
+    } else {
+        match parse_plus_code(input) {
+            Err(CoordsError::NotCoordinates) => parse_degrees(input)?,
+            result => result?,
+        }
+    };
+
+    validate(lat, lon)
+}
+
+/// "47.1600°N, 27.5800°E"
+pub fn format_coords(lat: f64, lon: f64) -> String {
+    format!(
+        "{:.4}°{}, {:.4}°{}",
+        lat.abs(),
+        if lat < 0.0 { 'S' } else { 'N' },
+        lon.abs(),
+        if lon < 0.0 { 'W' } else { 'E' }
+    )
+}
+
+pub(crate) fn validate(lat: f64, lon: f64) -> Result<(f64, f64), CoordsError> {
+    if !lat.is_finite() || !(-90.0..=90.0).contains(&lat) {
+        return Err(CoordsError::Invalid(format!(
+            "Latitude {lat} is out of range, it must be between -90 and 90"
+        )));
+    }
+    if !lon.is_finite() || !(-180.0..=180.0).contains(&lon) {
+        return Err(CoordsError::Invalid(format!(
+            "Longitude {lon} is out of range, it must be between -180 and 180"
+        )));
+    }
+    Ok((lat, lon))
+}
+
+// RFC 5870: geo:lat,lon[,alt][;crs=wgs84][;u=uncertainty]. Android also sends
+// geo:0,0?q=lat,lon(label) when only the query carries the point.
+fn parse_geo_uri(rest: &str) -> Result<(f64, f64), CoordsError> {
+    let invalid = || CoordsError::Invalid(format!("Can't read the geo: link 'geo:{rest}'"));
+
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let mut params = path.split(';');
+    let point = params.next().unwrap_or_default();
+    for param in params {
+        if let Some(("crs", crs)) = param.split_once('=')
+            && !crs.eq_ignore_ascii_case("wgs84")
+        {
+            return Err(CoordsError::Invalid(format!(
+                "Only WGS 84 geo: links are supported, not {crs}"
+            )));
+        }
+    }
+
+    let numbers: Vec<f64> = point
+        .split(',')
+        .map(|part| part.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    let (lat, lon) = match numbers.as_slice() {
+        [lat, lon] | [lat, lon, _] => (*lat, *lon),
+        _ => return Err(invalid()),
+    };
+
+    if lat == 0.0 && lon == 0.0 {
+        let q = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("q="))
+            .map(|q| {
+                q.split('(')
+                    .next()
+                    .unwrap_or(q)
+                    .replace("%2C", ",")
+                    .replace('+', " ")
+            });
+        if let Some(q) = q {
+            return parse_degrees(&q).map_err(|_| invalid());
+        }
+    }
+
+    Ok((lat, lon))
+}
+
+const PLUS_CODE_ALPHABET: &str = "23456789CFGHJMPQRVWX";
+const PLUS_CODE_SEPARATOR_POSITION: usize = 8;
+// degrees covered by one digit of each of the first five pairs
+const PLUS_CODE_PAIR_RESOLUTIONS: [f64; 5] = [20.0, 1.0, 0.05, 0.0025, 0.000125];
+const PLUS_CODE_GRID_ROWS: f64 = 5.0;
+const PLUS_CODE_GRID_COLUMNS: f64 = 4.0;
+
+// Open Location Code, decoded to the center of its area
+fn parse_plus_code(input: &str) -> Result<(f64, f64), CoordsError> {
+    let code = input.to_ascii_uppercase();
+    let Some((before, after)) = code.split_once('+') else {
+        return Err(CoordsError::NotCoordinates);
+    };
+    let is_code_char = |c: char| PLUS_CODE_ALPHABET.contains(c) || c == '0';
+    if before.is_empty() || !before.chars().chain(after.chars()).all(is_code_char) {
+        return Err(CoordsError::NotCoordinates);
+    }
+
+    if before.len() < PLUS_CODE_SEPARATOR_POSITION {
+        return Err(CoordsError::Invalid(format!(
+            "{input} is a short plus code, paste the full one with the area code in front"
+        )));
+    }
+    let invalid = || CoordsError::Invalid(format!("{input} is not a valid plus code"));
+    if before.len() > PLUS_CODE_SEPARATOR_POSITION
+        || after.len() == 1
+        || (before.ends_with('0') && !after.is_empty())
+    {
+        return Err(invalid());
+    }
+
+    // padded codes ("8GR60000+") describe a bigger area and stop at the first zero
+    let digits: String = before.trim_end_matches('0').to_string() + after;
+    if digits.contains('0') || digits.len() % 2 == 1 && digits.len() < 10 {
+        return Err(invalid());
+    }
+    let values: Vec<usize> = digits
+        .chars()
+        .filter_map(|c| PLUS_CODE_ALPHABET.find(c))
+        .collect();
+
+    let (mut lat, mut lon) = (-90.0, -180.0);
+    let (mut lat_size, mut lon_size) = (0.0, 0.0);
+    for (pair, resolution) in values.chunks(2).take(5).zip(PLUS_CODE_PAIR_RESOLUTIONS) {
+        let [lat_digit, lon_digit] = pair else {
+            return Err(invalid());
+        };
+        lat += *lat_digit as f64 * resolution;
+        lon += *lon_digit as f64 * resolution;
+        (lat_size, lon_size) = (resolution, resolution);
+    }
+    for &value in values.iter().skip(10) {
+        lat_size /= PLUS_CODE_GRID_ROWS;
+        lon_size /= PLUS_CODE_GRID_COLUMNS;
+        lat += (value / PLUS_CODE_GRID_COLUMNS as usize) as f64 * lat_size;
+        lon += (value % PLUS_CODE_GRID_COLUMNS as usize) as f64 * lon_size;
+    }
+
+    if values.first().is_some_and(|&v| v >= 9) || values.get(1).is_some_and(|&v| v >= 18) {
+        return Err(invalid());
+    }
+    Ok(((lat + lat_size / 2.0).min(90.0), lon + lon_size / 2.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    /// °, ' or ", as 0, 1, 2
+    Unit(usize),
+    Hemisphere(char),
+    Separator,
+}
+
+// Decimal degrees or degrees/minutes/seconds, with signs or N/S/E/W on either side:
+// "47.16, 27.58", "-33.86 151.2", 47°09'36.5"N 27°35'24"E, "N 47° 9.6' E 27° 35.4'"
+fn parse_degrees(input: &str) -> Result<(f64, f64), CoordsError> {
+    let tokens = tokenize(input).ok_or(CoordsError::NotCoordinates)?;
+    // a lone number is more likely a postal code than half a coordinate
+    let numbers = tokens
+        .iter()
+        .filter(|t| matches!(t, Token::Number(_)))
+        .count();
+    if numbers == 0 || numbers == 1 && tokens.len() == 1 {
+        return Err(CoordsError::NotCoordinates);
+    }
+    let invalid = || CoordsError::Invalid(format!("Can't read '{input}' as coordinates"));
+
+    let parts = split_parts(&tokens).ok_or_else(invalid)?;
+    let [first, second] = parts.as_slice() else {
+        return Err(invalid());
+    };
+
+    let first = first.degrees().ok_or_else(invalid)?;
+    let second = second.degrees().ok_or_else(invalid)?;
+    match (first.1, second.1) {
+        // "27°35'E 47°09'N" is unusual but unambiguous
+        (Some('E' | 'W'), Some('N' | 'S')) => Ok((second.0, first.0)),
+        (Some('E' | 'W'), _) | (_, Some('N' | 'S')) => Err(invalid()),
+        _ => Ok((first.0, second.0)),
+    }
+}
+
+fn tokenize(input: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            ',' | ';' | '/' => tokens.push(Token::Separator),
+            '°' | 'º' => tokens.push(Token::Unit(0)),
+            '\'' | '′' | '’' => tokens.push(Token::Unit(1)),
+            '"' | '″' | '”' => tokens.push(Token::Unit(2)),
+            'N' | 'S' | 'E' | 'W' | 'n' | 's' | 'e' | 'w' => {
+                tokens.push(Token::Hemisphere(c.to_ascii_uppercase()))
+            }
+            '-' | '+' | '.' | '0'..='9' => {
+                let mut number = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_ascii_digit() || next == '.') {
+                        break;
+                    }
+                    number.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Number(number.parse().ok()?));
+            }
+            _ => return None,
+        }
+    }
+
+    // '' used as a seconds mark
+    let mut merged: Vec<Token> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if token == Token::Unit(1) && merged.last() == Some(&Token::Unit(1)) {
+            *merged.last_mut()? = Token::Unit(2);
+        } else {
+            merged.push(token);
+        }
+    }
+    Some(merged)
+}
+
+#[derive(Default)]
+struct Part {
+    /// Degrees, minutes, seconds, in whatever order the unit marks put them
+    values: [Option<f64>; 3],
+    count: usize,
+    hemisphere: Option<char>,
+}
+
+impl Part {
+    fn push(&mut self, value: f64, unit: Option<usize>) -> Option<()> {
+        let slot = unit.unwrap_or(self.count);
+        if slot >= 3 || self.values[slot].is_some() {
+            return None;
+        }
+        self.values[slot] = Some(value);
+        self.count = slot + 1;
+        Some(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.iter().all(Option::is_none)
+    }
+
+    /// Signed decimal degrees and the hemisphere letter, if there was one
+    fn degrees(&self) -> Option<(f64, Option<char>)> {
+        let [degrees, minutes, seconds] = self.values;
+        let degrees = degrees?;
+        let minutes = minutes.unwrap_or(0.0);
+        let seconds = seconds.unwrap_or(0.0);
+        if !(0.0..60.0).contains(&minutes) || !(0.0..60.0).contains(&seconds) {
+            return None;
+        }
+        // minutes and seconds only make sense after whole degrees
+        if (self.values[1].is_some() || self.values[2].is_some()) && degrees.fract() != 0.0 {
+            return None;
+        }
+
+        let magnitude = degrees.abs() + minutes / 60.0 + seconds / 3600.0;
+        let negative = degrees.is_sign_negative() || matches!(self.hemisphere, Some('S' | 'W'));
+        if degrees.is_sign_negative() && self.hemisphere.is_some() {
+            return None;
+        }
+        Some((
+            if negative { -magnitude } else { magnitude },
+            self.hemisphere,
+        ))
+    }
+}
+
+fn split_parts(tokens: &[Token]) -> Option<Vec<Part>> {
+    let leading_hemispheres = matches!(tokens.first(), Some(Token::Hemisphere(_)));
+    let has_hemispheres = tokens.iter().any(|t| matches!(t, Token::Hemisphere(_)));
+    let has_units = tokens.iter().any(|t| matches!(t, Token::Unit(_)));
+
+    let mut parts = vec![Part::default()];
+    let mut i = 0;
+    while i < tokens.len() {
+        let current = parts.last_mut()?;
+        match tokens[i] {
+            // "47.16N, 27.58E": the N already ended the first part
+            Token::Separator if current.is_empty() && current.hemisphere.is_none() => {
+                if parts.len() == 1 {
+                    return None;
+                }
+            }
+            Token::Separator => parts.push(Part::default()),
+            Token::Hemisphere(h) if leading_hemispheres => {
+                if !current.is_empty() {
+                    parts.push(Part::default());
+                }
+                parts.last_mut()?.hemisphere = Some(h);
+            }
+            Token::Hemisphere(h) => {
+                if current.is_empty() || current.hemisphere.is_some() {
+                    return None;
+                }
+                current.hemisphere = Some(h);
+                parts.push(Part::default());
+            }
+            Token::Number(value) => {
+                let unit = match tokens.get(i + 1) {
+                    Some(Token::Unit(unit)) => {
+                        i += 1;
+                        Some(*unit)
+                    }
+                    _ => None,
+                };
+                // without N/S/E/W or ° to group them, every number is a coordinate of its own
+                let starts_new =
+                    !current.is_empty() && (unit == Some(0) || (!has_hemispheres && !has_units));
+                if starts_new {
+                    parts.push(Part::default());
+                }
+                parts.last_mut()?.push(value, unit)?;
+            }
+            Token::Unit(_) => return None,
+        }
+        i += 1;
+    }
+
+    parts.retain(|part| !part.is_empty() || part.hemisphere.is_some());
+    Some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(input: &str, (lat, lon): (f64, f64)) {
+        let (got_lat, got_lon) = parse_coords(input).unwrap_or_else(|e| panic!("{input}: {e:?}"));
+        assert!(
+            (got_lat - lat).abs() < 1e-6 && (got_lon - lon).abs() < 1e-6,
+            "{input} gave ({got_lat}, {got_lon}), expected ({lat}, {lon})"
+        );
+    }
+
+    fn assert_invalid(input: &str, message: &str) {
+        match parse_coords(input) {
+            Err(CoordsError::Invalid(e)) => assert!(e.contains(message), "{input}: {e}"),
+            other => panic!("{input} gave {other:?}"),
+        }
+    }
+
+    #[test]
+    fn decimal_and_dms_forms() {
+        let cases = [
+            ("47.16,27.58", (47.16, 27.58)),
+            ("  -33.86 151.2 ", (-33.86, 151.2)),
+            ("47.16; 27.58", (47.16, 27.58)),
+            ("47.16N, 27.58E", (47.16, 27.58)),
+            ("33.86S 151.2E", (-33.86, 151.2)),
+            ("40.7128° N, 74.0060° W", (40.7128, -74.006)),
+            ("s 22.9 w 43.2", (-22.9, -43.2)),
+            ("47°09'36\"N 27°35'24\"E", (47.16, 27.59)),
+            ("47°09′36″N 27°35′24″E", (47.16, 27.59)),
+            ("47°09'36''N 27°35'24''E", (47.16, 27.59)),
+            ("47 9 36 N 27 35 24 E", (47.16, 27.59)),
+            ("N 47° 9.6' E 27° 35.4'", (47.16, 27.59)),
+            // longitude first is fine as long as the letters say so
+            ("27°35'24\"E 47°09'36\"N", (47.16, 27.59)),
+        ];
+        for (input, expected) in cases {
+            assert_close(input, expected);
+        }
+    }
+
+    #[test]
+    fn geo_uris() {
+        let cases = [
+            ("geo:47.16,27.58", (47.16, 27.58)),
+            ("GEO:47.16,27.58,120;u=35", (47.16, 27.58)),
+            ("geo:-33.86,151.2;crs=WGS84", (-33.86, 151.2)),
+            ("geo:0,0?q=47.16%2C27.58(Iasi)", (47.16, 27.58)),
+            ("geo:0,0", (0.0, 0.0)),
+        ];
+        for (input, expected) in cases {
+            assert_close(input, expected);
+        }
+
+        assert_invalid("geo:47.16,27.58;crs=utm", "Only WGS 84");
+        assert_invalid("geo:47.16", "Can't read the geo: link");
+        assert_invalid("geo:0,0?q=Iasi", "Can't read the geo: link");
+    }
+
+    #[test]
+    fn full_plus_codes_decode_to_the_center() {
+        let cases = [
+            ("8FVC9G8F+6X", (47.3655625, 8.5249375)),
+            ("8fvc9g8f+6xr", (47.3656125, 8.524890625)),
+            // padded, a 1° by 1° area
+            ("8FVC0000+", (47.5, 8.5)),
+        ];
+        for (input, expected) in cases {
+            assert_close(input, expected);
+        }
+    }
+
+    #[test]
+    fn unusable_plus_codes() {
+        let cases = [
+            ("8GR6+5X", "short plus code"),
+            ("8FVC9G8F+6", "not a valid plus code"),
+            ("8FVC9G80+6X", "not a valid plus code"),
+            ("8FVC9G8F2+6X", "not a valid plus code"),
+            // the first digit would be past the north pole
+            ("XFVC9G8F+6X", "not a valid plus code"),
+        ];
+        for (input, message) in cases {
+            assert_invalid(input, message);
+        }
+    }
+
+    #[test]
+    fn out_of_range_and_garbled_coordinates() {
+        let cases = [
+            ("95, 27.6", "Latitude 95 is out of range"),
+            ("-90.5 0", "Latitude -90.5 is out of range"),
+            ("47, 227", "Longitude 227 is out of range"),
+            ("47N 181W", "Longitude -181 is out of range"),
+            ("geo:91,0", "Latitude 91 is out of range"),
+            ("-47.16S 27.58E", "Can't read"),
+            ("47.16, 27.58, 12", "Can't read"),
+            ("47°61'N 27°E", "Can't read"),
+            ("47.5°30'N 27°E", "Can't read"),
+            ("47.16E, 27.58E", "Can't read"),
+            ("47.16N 27.58N", "Can't read"),
+        ];
+        for (input, message) in cases {
+            assert_invalid(input, message);
+        }
+    }
+
+    #[test]
+    fn names_stay_names() {
+        let cases = [
+            "",
+            "Paris",
+            "St. Louis",
+            "10001",
+            "Route 66",
+            "I-95",
+            "W 42nd St",
+            "New York, NY",
+            // not a char boundary where "geo:" would end
+            "Brașov",
+            "C++ conference",
+        ];
+        for input in cases {
+            assert_eq!(
+                parse_coords(input),
+                Err(CoordsError::NotCoordinates),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn formatted_coordinates_read_back() {
+        assert_eq!(format_coords(-33.86, 151.2), "33.8600°S, 151.2000°E");
+        assert_close(&format_coords(-33.86, -151.2), (-33.86, -151.2));
+    }
+}
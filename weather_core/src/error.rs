@@ -0,0 +1,284 @@
+use reqwest::StatusCode;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Everything that can go wrong while asking a provider for weather data.
+/// `Display` is meant for logs, `user_message` for the UI.
+#[derive(Error, Debug, Clone)]
+pub enum WeatherError {
+    #[error("network error: {0}")]
+    Network(String),
+
+    #[error("request timed out")]
+    Timeout,
+
+    #[error("API key rejected (HTTP {0})")]
+    Auth(u16),
+
+    #[error("rate limited")]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("location not found")]
+    NotFound,
+
+    #[error("unexpected response: {0}")]
+    Parse(String),
+
+    #[error("provider outage (HTTP {0})")]
+    ProviderOutage(u16),
+
+    #[error("request rejected (HTTP {status}): {message}")]
+    Rejected { status: u16, message: String },
+
+    #[error("offline mode and nothing cached for this request")]
+    Offline,
+
+    #[error("provider paused after repeated failures, retrying in {retry_in:.0?}")]
+    CircuitOpen { retry_in: Duration },
+}
+
+impl WeatherError {
+    /// Maps a non-2xx response to an error. `body` is the provider's error text,
+    /// which is the only way to tell a bad location from other bad requests.
+    pub fn from_status(status: StatusCode, retry_after: Option<Duration>, body: &str) -> Self {
+        match status.as_u16() {
+            401 | 403 => Self::Auth(status.as_u16()),
+            404 => Self::NotFound,
+            429 => Self::RateLimited { retry_after },
+            // Visual Crossing answers "Bad API Request:Invalid location parameter value."
+            400 if body.to_lowercase().contains("location") => Self::NotFound,
+            code if status.is_server_error() => Self::ProviderOutage(code),
+            code => Self::Rejected {
+                status: code,
+                message: body.trim().chars().take(200).collect(),
+            },
+        }
+    }
+
+    /// Errors worth retrying or falling back to a stale cache entry for
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Network(_)
+                | Self::Timeout
+                | Self::RateLimited { .. }
+                | Self::ProviderOutage(_)
+                | Self::CircuitOpen { .. }
+        )
+    }
+
+    pub fn user_message(&self) -> String {
+        match self {
+            Self::Network(_) => {
+                "Can't reach the weather service. Check your internet connection.".to_string()
+            }
+            Self::Timeout => "The weather service took too long to answer. Try again.".to_string(),
+            Self::Auth(_) => {
+                "The API key was rejected. Check that it's correct and hasn't expired.".to_string()
+            }
+            Self::RateLimited {
+                retry_after: Some(wait),
+            } => format!(
+                "Too many requests, try again in {} min.",
+                wait.as_secs().div_ceil(60).max(1)
+            ),
+            Self::RateLimited { retry_after: None } => {
+                "Too many requests for today's quota. Try again later.".to_string()
+            }
+            Self::NotFound => "Searched location does not exist!".to_string(),
+            Self::Parse(_) => {
+                "The weather service sent data the app doesn't understand.".to_string()
+            }
+            Self::ProviderOutage(_) => {
+                "The weather service is having problems. Try again later.".to_string()
+            }
+            Self::Rejected { message, .. } if !message.is_empty() => {
+                format!("The weather service refused the request: {message}")
+            }
+            Self::Rejected { .. } => "The weather service refused the request.".to_string(),
+            Self::Offline => "Offline, and this location hasn't been cached yet.".to_string(),
+            Self::CircuitOpen { retry_in } => format!(
+                "The weather service keeps failing, trying again in {} min.",
+                retry_in.as_secs().div_ceil(60).max(1)
+            ),
+        }
+    }
+}
+
+impl From<reqwest::Error> for WeatherError {
+    fn from(e: reqwest::Error) -> Self {
+        // the URL can carry the API key, keep it out of messages and logs
+        let e = e.without_url();
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_decode() {
+            Self::Parse(e.to_string())
+        } else {
+            // "error sending request" alone doesn't say whether the proxy, DNS or TLS failed
+            let mut message = e.to_string();
+            let mut source = std::error::Error::source(&e);
+            while let Some(cause) = source {
+                message = format!("{message}: {cause}");
+                source = cause.source();
+            }
+            Self::Network(message)
+        }
+    }
+}
+
+impl From<serde_json::Error> for WeatherError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Parse(e.to_string())
+    }
+}
+
+/// Reads `Retry-After`, given either in seconds or as an HTTP date
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    // "Wed, 21 Oct 2015 07:28:00 GMT"; a date in the past means now
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn statuses_map_to_errors() {
+        use WeatherError::*;
+        let wait = Some(Duration::from_secs(30));
+        let cases = [
+            (401, "", Auth(401)),
+            (403, "", Auth(403)),
+            (404, "", NotFound),
+            (
+                400,
+                "Bad API Request:Invalid location parameter value.",
+                NotFound,
+            ),
+            (
+                400,
+                "  Bad API Request:Invalid date  ",
+                Rejected {
+                    status: 400,
+                    message: "Bad API Request:Invalid date".to_string(),
+                },
+            ),
+            (429, "", RateLimited { retry_after: wait }),
+            (500, "", ProviderOutage(500)),
+            (502, "", ProviderOutage(502)),
+            (503, "", ProviderOutage(503)),
+            (504, "", ProviderOutage(504)),
+        ];
+
+        for (status, body, expected) in cases {
+            let error =
+                WeatherError::from_status(StatusCode::from_u16(status).unwrap(), wait, body);
+            // WeatherError has no PartialEq, but Debug shows every field
+            assert_eq!(
+                format!("{error:?}"),
+                format!("{expected:?}"),
+                "HTTP {status}"
+            );
+            assert_eq!(
+                error.is_transient(),
+                status == 429 || status >= 500,
+                "HTTP {status}"
+            );
+        }
+    }
+
+    #[test]
+    fn only_passing_failures_are_transient() {
+        let transient = [
+            WeatherError::Network("connection reset".to_string()),
+            WeatherError::Timeout,
+            WeatherError::RateLimited { retry_after: None },
+            WeatherError::ProviderOutage(503),
+            WeatherError::CircuitOpen {
+                retry_in: Duration::from_secs(60),
+            },
+        ];
+        let permanent = [
+            WeatherError::Auth(401),
+            WeatherError::NotFound,
+            WeatherError::Parse("expected value".to_string()),
+            WeatherError::Rejected {
+                status: 400,
+                message: String::new(),
+            },
+            WeatherError::Offline,
+        ];
+
+        for error in transient {
+            assert!(error.is_transient(), "{error:?}");
+        }
+        for error in permanent {
+            assert!(!error.is_transient(), "{error:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn timeouts_become_timeout_errors() {
+        // accepts the connection but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+
+        let error: WeatherError = client.get(url).send().await.unwrap_err().into();
+        assert!(matches!(error, WeatherError::Timeout), "{error:?}");
+        assert!(error.is_transient());
+        drop(listener);
+    }
+
+    #[test]
+    fn retry_after_reads_seconds_and_dates() {
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+        assert_eq!(retry_after(&headers("120")), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(&headers(" 0 ")), Some(Duration::ZERO));
+
+        let at = chrono::Utc::now() + chrono::Duration::seconds(90);
+        let wait = retry_after(&headers(
+            &at.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        ))
+        .expect("an HTTP date");
+        assert!(
+            (Duration::from_secs(85)..=Duration::from_secs(90)).contains(&wait),
+            "waiting {wait:?}"
+        );
+
+        // already passed, so no waiting
+        assert_eq!(
+            retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+
+        for garbage in ["soon", "-5", "1.5", "", "Wed, 32 Oct 2015"] {
+            assert_eq!(retry_after(&headers(garbage)), None, "{garbage:?}");
+        }
+    }
+}
@@ -0,0 +1,631 @@
+use super::{FavoritesError, FavoritesFile, SCHEMA_VERSION, migrate};
+use crate::coords;
+use crate::format::csv_field;
+use crate::paths::write_atomically;
+use crate::weather_structs::FavoriteLocation;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+
+/// File formats the favorites can be imported from and exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExchangeFormat {
+    /// The app's own favorites.json
+    Json,
+    /// name, address, lat, lon, tags; tags separated by semicolons
+    Csv,
+    /// A FeatureCollection of Points
+    GeoJson,
+    /// Waypoints
+    Gpx,
+}
+
+impl ExchangeFormat {
+    pub const ALL: [ExchangeFormat; 4] = [
+        ExchangeFormat::Json,
+        ExchangeFormat::Csv,
+        ExchangeFormat::GeoJson,
+        ExchangeFormat::Gpx,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExchangeFormat::Json => "JSON",
+            ExchangeFormat::Csv => "CSV",
+            ExchangeFormat::GeoJson => "GeoJSON",
+            ExchangeFormat::Gpx => "GPX",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<ExchangeFormat> {
+        Self::ALL.into_iter().find(|format| format.label() == label)
+    }
+
+    /// .json, .csv, .geojson or .gpx
+    pub fn from_path(path: &Path) -> Option<ExchangeFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(ExchangeFormat::Json),
+            "csv" => Some(ExchangeFormat::Csv),
+            "geojson" => Some(ExchangeFormat::GeoJson),
+            "gpx" => Some(ExchangeFormat::Gpx),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for ExchangeFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ExchangeFormat::Json),
+            "csv" => Ok(ExchangeFormat::Csv),
+            "geojson" => Ok(ExchangeFormat::GeoJson),
+            "gpx" => Ok(ExchangeFormat::Gpx),
+            _ => Err(anyhow::anyhow!(
+                "Unknown format '{s}' (expected json, csv, geojson or gpx)"
+            )),
+        }
+    }
+}
+
+/// `format`, or the one the file extension names
+pub fn resolve_format(
+    path: &Path,
+    format: Option<ExchangeFormat>,
+) -> Result<ExchangeFormat, anyhow::Error> {
+    format.or_else(|| ExchangeFormat::from_path(path)).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Can't tell the format of {} from its extension, pick one (json, csv, geojson or gpx)",
+            path.display()
+        )
+    })
+}
+
+/// Reads favorites from a file in any of the formats, without touching the saved ones
+pub fn import_file(
+    path: &Path,
+    format: Option<ExchangeFormat>,
+) -> Result<Vec<FavoriteLocation>, anyhow::Error> {
+    let format = resolve_format(path, format)?;
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Can't read {}: {e}", path.display()))?;
+
+    let favorites = match format {
+        ExchangeFormat::Json => parse_json(&content, path),
+        ExchangeFormat::Csv => parse_csv(&content),
+        ExchangeFormat::GeoJson => parse_geojson(&content),
+        ExchangeFormat::Gpx => parse_gpx(&content),
+    }
+    .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+
+    if favorites.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} has no places in it as {}",
+            path.display(),
+            format.label()
+        ));
+    }
+    Ok(favorites)
+}
+
+pub fn export(favorites: &[FavoriteLocation], format: ExchangeFormat) -> String {
+    match format {
+        ExchangeFormat::Json => to_json(favorites),
+        ExchangeFormat::Csv => to_csv(favorites),
+        ExchangeFormat::GeoJson => to_geojson(favorites),
+        ExchangeFormat::Gpx => to_gpx(favorites),
+    }
+}
+
+/// Replaces `path` in one step, so a failed write leaves whatever was there
+pub fn export_file(
+    path: &Path,
+    favorites: &[FavoriteLocation],
+    format: Option<ExchangeFormat>,
+) -> Result<(), anyhow::Error> {
+    let format = resolve_format(path, format)?;
+    write_atomically(path, export(favorites, format).as_bytes())
+        .map_err(|e| anyhow::anyhow!("Can't write {}: {e}", path.display()))
+}
+
+// Imported places keep the file's name for display and its address, when it has one,
+// as what the provider would call it
+fn favorite(name: Option<String>, address: Option<String>, lat: f64, lon: f64) -> FavoriteLocation {
+    let address = address.filter(|address| !address.trim().is_empty());
+    let name = name.filter(|name| !name.trim().is_empty());
+    let mut favorite = match (&name, address) {
+        (_, Some(address)) => FavoriteLocation::new(address, lat, lon),
+        (Some(name), None) => FavoriteLocation::new(name.clone(), lat, lon),
+        (None, None) => FavoriteLocation::new(coords::format_coords(lat, lon), lat, lon),
+    };
+    favorite.display_name = name.filter(|name| *name != favorite.address);
+    favorite
+}
+
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split([';', '|'])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn check_point(lat: f64, lon: f64) -> Result<(), String> {
+    coords::validate(lat, lon)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// JSON
+
+fn parse_json(content: &str, path: &Path) -> Result<Vec<FavoriteLocation>, String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    // the store's messages are about its own file, which isn't this one
+    let (file, _) = migrate(value, path).map_err(|e| match e {
+        FavoritesError::Corrupt { reason, .. } => reason,
+        FavoritesError::NewerVersion { version, .. } => {
+            format!("saved by a newer version of the app (schema {version})")
+        }
+        e => e.to_string(),
+    })?;
+    for favorite in &file.favorites {
+        check_point(favorite.lat, favorite.lon)
+            .map_err(|e| format!("{}: {e}", favorite.address))?;
+    }
+    Ok(file.favorites)
+}
+
+fn to_json(favorites: &[FavoriteLocation]) -> String {
+    let file = FavoritesFile {
+        version: SCHEMA_VERSION,
+        favorites: favorites.to_vec(),
+    };
+    serde_json::to_string_pretty(&file).unwrap_or_default() + "\n"
+}
+
+// CSV
+
+const CSV_NAME_COLUMNS: [&str; 4] = ["name", "title", "label", "display_name"];
+const CSV_ADDRESS_COLUMNS: [&str; 3] = ["address", "location", "place"];
+const CSV_LAT_COLUMNS: [&str; 3] = ["lat", "latitude", "y"];
+const CSV_LON_COLUMNS: [&str; 5] = ["lon", "lng", "long", "longitude", "x"];
+const CSV_TAG_COLUMNS: [&str; 5] = ["tags", "group", "groups", "folder", "category"];
+
+// Columns are found by their header, so spreadsheets with extra columns or another
+// order import as they are
+fn parse_csv(content: &str) -> Result<Vec<FavoriteLocation>, String> {
+    let mut rows = csv_rows(content)?.into_iter();
+    let Some((_, header)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = header
+        .iter()
+        .map(|column| column.trim().to_ascii_lowercase())
+        .collect();
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|column| names.contains(&column.as_str()))
+    };
+
+    let (Some(lat_column), Some(lon_column)) = (column(&CSV_LAT_COLUMNS), column(&CSV_LON_COLUMNS))
+    else {
+        return Err("the first row needs lat and lon columns".to_string());
+    };
+    let name_column = column(&CSV_NAME_COLUMNS);
+    let address_column = column(&CSV_ADDRESS_COLUMNS);
+    let tag_column = column(&CSV_TAG_COLUMNS);
+
+    let mut favorites = Vec::new();
+    for (line, row) in rows {
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| row.get(index))
+                .map(|field| field.trim().to_string())
+        };
+        let number = |index: usize, what: &str| -> Result<f64, String> {
+            let text = field(Some(index)).unwrap_or_default();
+            text.parse()
+                .map_err(|_| format!("line {line}: {what} '{text}' isn't a number"))
+        };
+
+        let lat = number(lat_column, "latitude")?;
+        let lon = number(lon_column, "longitude")?;
+        check_point(lat, lon).map_err(|e| format!("line {line}: {e}"))?;
+
+        let mut favorite = favorite(field(name_column), field(address_column), lat, lon);
+        favorite.tags = field(tag_column)
+            .map(|tags| split_tags(&tags))
+            .unwrap_or_default();
+        favorites.push(favorite);
+    }
+    Ok(favorites)
+}
+
+// RFC 4180: quoted fields may hold commas, doubled quotes and line breaks. Each row
+// comes with the line it starts on.
+fn csv_rows(content: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => row.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push((row_line, std::mem::take(&mut row)));
+                line += 1;
+                row_line = line;
+            }
+            '\n' => {
+                field.push(c);
+                line += 1;
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!("line {row_line}: a quoted field is never closed"));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+fn to_csv(favorites: &[FavoriteLocation]) -> String {
+    let mut out = String::from("name,address,lat,lon,tags\n");
+    for favorite in favorites {
+        out += &format!(
+            "{},{},{},{},{}\n",
+            csv_field(favorite.name()),
+            csv_field(&favorite.address),
+            favorite.lat,
+            favorite.lon,
+            csv_field(&favorite.tags.join(";"))
+        );
+    }
+    out
+}
+
+// GeoJSON
+
+fn parse_geojson(content: &str) -> Result<Vec<FavoriteLocation>, String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let features = match value.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => value
+            .get("features")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default(),
+        Some("Feature") => vec![value],
+        _ => return Err("not a GeoJSON FeatureCollection".to_string()),
+    };
+
+    let mut favorites = Vec::new();
+    for (index, feature) in features.iter().enumerate() {
+        let geometry = &feature["geometry"];
+        // lines and areas have no single point to show weather for
+        if geometry["type"] != "Point" {
+            continue;
+        }
+        let (Some(lon), Some(lat)) = (
+            geometry["coordinates"][0].as_f64(),
+            geometry["coordinates"][1].as_f64(),
+        ) else {
+            return Err(format!(
+                "feature {}: the point has no coordinates",
+                index + 1
+            ));
+        };
+        check_point(lat, lon).map_err(|e| format!("feature {}: {e}", index + 1))?;
+
+        let properties = &feature["properties"];
+        let text = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| properties[*key].as_str())
+                .map(str::to_string)
+        };
+        let mut favorite = favorite(
+            text(&["name", "title"]),
+            text(&["address", "description"]),
+            lat,
+            lon,
+        );
+        favorite.tags = match &properties["tags"] {
+            Value::Array(tags) => tags
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            Value::String(tags) => split_tags(tags),
+            _ => Vec::new(),
+        };
+        favorites.push(favorite);
+    }
+    Ok(favorites)
+}
+
+fn to_geojson(favorites: &[FavoriteLocation]) -> String {
+    let features: Vec<Value> = favorites
+        .iter()
+        .map(|favorite| {
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [favorite.lon, favorite.lat] },
+                "properties": {
+                    "name": favorite.name(),
+                    "address": favorite.address,
+                    "tags": favorite.tags,
+                },
+            })
+        })
+        .collect();
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    serde_json::to_string_pretty(&collection).unwrap_or_default() + "\n"
+}
+
+// GPX
+
+fn parse_gpx(content: &str) -> Result<Vec<FavoriteLocation>, String> {
+    let document = roxmltree::Document::parse(content).map_err(|e| e.to_string())?;
+    if document.root_element().tag_name().name() != "gpx" {
+        return Err("not a GPX file".to_string());
+    }
+
+    let mut favorites = Vec::new();
+    for waypoint in document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "wpt")
+    {
+        let line = document.text_pos_at(waypoint.range().start).row;
+        let coordinate = |name: &str| -> Result<f64, String> {
+            let text = waypoint.attribute(name).unwrap_or_default();
+            text.trim()
+                .parse()
+                .map_err(|_| format!("line {line}: {name} '{text}' isn't a number"))
+        };
+        let lat = coordinate("lat")?;
+        let lon = coordinate("lon")?;
+        check_point(lat, lon).map_err(|e| format!("line {line}: {e}"))?;
+
+        let child = |name: &str| {
+            waypoint
+                .children()
+                .find(|node| node.tag_name().name() == name)
+                .and_then(|node| node.text())
+                .map(|text| text.trim().to_string())
+        };
+        let mut favorite = favorite(child("name"), child("desc"), lat, lon);
+        favorite.tags = child("type")
+            .map(|tags| split_tags(&tags))
+            .unwrap_or_default();
+        favorites.push(favorite);
+    }
+    Ok(favorites)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn to_gpx(favorites: &[FavoriteLocation]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gpx version=\"1.1\" creator=\"rust-weather-app\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    );
+    for favorite in favorites {
+        out += &format!(
+            "  <wpt lat=\"{}\" lon=\"{}\">\n    <name>{}</name>\n",
+            favorite.lat,
+            favorite.lon,
+            xml_escape(favorite.name())
+        );
+        // renamed places keep what the provider calls them, which is read back on import
+        if favorite.display_name.is_some() {
+            out += &format!("    <desc>{}</desc>\n", xml_escape(&favorite.address));
+        }
+        if !favorite.tags.is_empty() {
+            out += &format!(
+                "    <type>{}</type>\n",
+                xml_escape(&favorite.tags.join(";"))
+            );
+        }
+        out += "  </wpt>\n";
+    }
+    out += "</gpx>\n";
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn favorites() -> Vec<FavoriteLocation> {
+        let mut home = FavoriteLocation::new("Iasi, Romania".to_string(), 47.1585, 27.6014);
+        home.display_name = Some("Home, \"the\" flat\nsecond floor".to_string());
+        home.tags = vec!["family".to_string(), "a, b".to_string()];
+        let cabin = FavoriteLocation::new("Bran <Brașov> & co".to_string(), 45.515, 25.367);
+        vec![home, cabin]
+    }
+
+    fn round_trip(format: ExchangeFormat) -> Vec<FavoriteLocation> {
+        let exported = export(&favorites(), format);
+        match format {
+            ExchangeFormat::Json => parse_json(&exported, Path::new("export.json")),
+            ExchangeFormat::Csv => parse_csv(&exported),
+            ExchangeFormat::GeoJson => parse_geojson(&exported),
+            ExchangeFormat::Gpx => parse_gpx(&exported),
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn every_format_reads_back_what_it_wrote() {
+        for format in ExchangeFormat::ALL {
+            assert_eq!(round_trip(format), favorites(), "{}", format.label());
+        }
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_line_breaks() {
+        let csv = to_csv(&favorites());
+
+        assert!(csv.starts_with(
+            "name,address,lat,lon,tags\n\"Home, \"\"the\"\" flat\nsecond floor\",\"Iasi, Romania\","
+        ));
+        assert!(csv.contains(",\"family;a, b\"\n"));
+    }
+
+    #[test]
+    fn csv_finds_columns_by_header() {
+        let csv = "\u{feff}Latitude,Notes,Longitude,Title,Group\r\n\
+                   47.16,,27.6,\"Iasi, \"\"old town\"\"\",home|work\r\n\
+                   \r\n\
+                   45.515,,25.367,,\n";
+
+        let favorites = parse_csv(csv).unwrap();
+
+        assert_eq!(favorites.len(), 2);
+        assert_eq!(favorites[0].name(), "Iasi, \"old town\"");
+        assert_eq!(favorites[0].tags, ["home", "work"]);
+        assert_eq!(favorites[1].address, coords::format_coords(45.515, 25.367));
+    }
+
+    #[test]
+    fn malformed_csv_is_an_error() {
+        let cases = [
+            ("name,lat\nIasi,47.16\n", "needs lat and lon"),
+            (
+                "name,lat,lon\nIasi,47.16,\n",
+                "line 2: longitude '' isn't a number",
+            ),
+            (
+                "name,lat,lon\nIasi,north,27.6\n",
+                "line 2: latitude 'north'",
+            ),
+            (
+                "name,lat,lon\n\"Iasi,47.16,27.6\n",
+                "line 2: a quoted field is never closed",
+            ),
+            (
+                "name,lat,lon\nIasi,47.16,27.6\nNowhere,95,27.6\n",
+                "line 3:",
+            ),
+        ];
+        for (csv, error) in cases {
+            let e = parse_csv(csv).unwrap_err();
+            assert!(e.contains(error), "{csv:?} gave {e:?}");
+        }
+    }
+
+    #[test]
+    fn geojson_skips_shapes_without_a_single_point() {
+        let geojson = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                { "type": "Feature",
+                  "geometry": { "type": "LineString", "coordinates": [[25.3, 45.5], [25.6, 45.6]] },
+                  "properties": { "name": "Ridge walk" } },
+                { "type": "Feature",
+                  "geometry": { "type": "Point", "coordinates": [25.367, 45.515] },
+                  "properties": { "title": "Bran", "tags": "castle; trips" } }
+            ]
+        }"#;
+
+        let favorites = parse_geojson(geojson).unwrap();
+
+        assert_eq!(favorites.len(), 1);
+        assert_eq!(favorites[0].name(), "Bran");
+        assert_eq!(favorites[0].tags, ["castle", "trips"]);
+    }
+
+    #[test]
+    fn malformed_geojson_is_an_error() {
+        let cases = [
+            (
+                r#"{"type": "Point", "coordinates": [27.6, 47.16]}"#,
+                "not a GeoJSON",
+            ),
+            (
+                r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [27.6]}}"#,
+                "feature 1: the point has no coordinates",
+            ),
+            (
+                r#"{"type": "Feature", "geometry": {"type": "Point", "coordinates": [227.6, 47.16]}}"#,
+                "feature 1:",
+            ),
+            (r#"{"type": "FeatureCollection", "features": ["#, "EOF"),
+        ];
+        for (geojson, error) in cases {
+            let e = parse_geojson(geojson).unwrap_err();
+            assert!(e.contains(error), "{geojson} gave {e:?}");
+        }
+    }
+
+    #[test]
+    fn gpx_waypoints_without_names_use_their_coordinates() {
+        let gpx = r#"<?xml version="1.0"?>
+<gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="45.515" lon="25.367"/>
+  <wpt lat="47.16" lon="27.6"><desc>Iasi</desc></wpt>
+  <trk><name>Not a place</name></trk>
+</gpx>"#;
+
+        let favorites = parse_gpx(gpx).unwrap();
+
+        assert_eq!(favorites.len(), 2);
+        assert_eq!(favorites[0].name(), coords::format_coords(45.515, 25.367));
+        assert_eq!(favorites[1].name(), "Iasi");
+    }
+
+    #[test]
+    fn malformed_gpx_is_an_error() {
+        let cases = [
+            ("<kml></kml>", "not a GPX file"),
+            (
+                "<gpx>\n  <wpt lon=\"25.367\"/>\n</gpx>",
+                "line 2: lat '' isn't a number",
+            ),
+        ];
+        for (gpx, error) in cases {
+            let e = parse_gpx(gpx).unwrap_err();
+            assert!(e.contains(error), "{gpx} gave {e:?}");
+        }
+        assert!(parse_gpx("<gpx><wpt lat=\"45.5\" lon=\"25.3\"></gpx>").is_err());
+    }
+
+    #[test]
+    fn export_file_replaces_the_target_in_one_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("favorites.gpx");
+        fs::write(&path, "old export").unwrap();
+
+        export_file(&path, &favorites(), None).unwrap();
+
+        assert!(!dir.path().join("favorites.gpx.tmp").exists());
+        assert_eq!(import_file(&path, None).unwrap(), favorites());
+        assert!(export_file(&dir.path().join("favorites.kml"), &favorites(), None).is_err());
+    }
+}
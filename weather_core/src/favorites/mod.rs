@@ -0,0 +1,564 @@
+use crate::config::Config;
+use crate::paths::{sibling_path, write_atomically};
+use crate::weather_structs::FavoriteLocation;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+mod formats;
+
+pub use formats::{ExchangeFormat, export, export_file, import_file, resolve_format};
+
+/// Bumped whenever the file layout changes, together with a new step in `migrate`
+pub const SCHEMA_VERSION: u64 = 2;
+/// How many earlier versions of the file are kept, as favorites.json.1 (newest) and up
+const BACKUPS: usize = 3;
+const FILE_NAME: &str = "favorites.json";
+/// Where favorites were kept before they moved to the data dir
+const LEGACY_PATH: &str = "./favorites.json";
+
+#[derive(Error, Debug)]
+pub enum FavoritesError {
+    #[error("can't read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("{} is damaged ({reason}); fix it or restore a backup, it won't be overwritten", path.display())]
+    Corrupt { path: PathBuf, reason: String },
+
+    #[error("{} is from a newer version of the app (schema {version}), it won't be overwritten", path.display())]
+    NewerVersion { path: PathBuf, version: u64 },
+
+    #[error("can't save {}: {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+
+    #[error("can't copy {} into {}: {source}", LEGACY_PATH, path.display())]
+    Import { path: PathBuf, source: io::Error },
+
+    #[error("no data directory, set XDG_DATA_HOME, HOME or data_dir")]
+    NoDataDir,
+
+    #[error("favorites aren't saved this session because the file couldn't be read")]
+    Unsaved,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FavoritesFile {
+    version: u64,
+    favorites: Vec<FavoriteLocation>,
+}
+
+/// The favorites, kept in memory and written through to disk on every change
+pub struct FavoritesStore {
+    /// None when the file on disk couldn't be read, so it is never written over
+    path: Option<PathBuf>,
+    favorites: Vec<FavoriteLocation>,
+    /// Schema the file was in when it had to be migrated. It is copied aside on the
+    /// first save, since the rotating backups would soon drop it.
+    migrated_from: Option<u64>,
+}
+
+impl FavoritesStore {
+    /// favorites.json in the data dir. The first time, the favorites.json older versions
+    /// left in the working directory is copied there; the old file is left alone.
+    pub fn from_config(config: &Config) -> Result<Self, FavoritesError> {
+        let path = config
+            .data_path(FILE_NAME)
+            .ok_or(FavoritesError::NoDataDir)?;
+
+        let legacy = Path::new(LEGACY_PATH);
+        if !path.exists() && legacy.exists() {
+            import_legacy(legacy, &path).map_err(|source| FavoritesError::Import {
+                path: path.clone(),
+                source,
+            })?;
+            eprintln!(
+                "Copied favorites from {} to {}, the old file can be deleted",
+                legacy.display(),
+                path.display()
+            );
+        }
+
+        Self::open(path)
+    }
+
+    /// A missing file is an empty list; anything that can't be read is an error, and
+    /// the file is left alone
+    pub fn open(path: PathBuf) -> Result<Self, FavoritesError> {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(source) => return Err(FavoritesError::Read { path, source }),
+        };
+
+        let (favorites, migrated_from) = if content.trim().is_empty() {
+            (Vec::new(), None)
+        } else {
+            let value: Value =
+                serde_json::from_str(&content).map_err(|e| FavoritesError::Corrupt {
+                    path: path.clone(),
+                    reason: e.to_string(),
+                })?;
+            let (file, version) = migrate(value, &path)?;
+            (
+                file.favorites,
+                (version != SCHEMA_VERSION).then_some(version),
+            )
+        };
+
+        Ok(FavoritesStore {
+            path: Some(path),
+            favorites,
+            migrated_from,
+        })
+    }
+
+    /// Starts empty and never touches the disk, for when `open` failed
+    pub fn unsaved() -> Self {
+        FavoritesStore {
+            path: None,
+            favorites: Vec::new(),
+            migrated_from: None,
+        }
+    }
+
+    pub fn favorites(&self) -> &[FavoriteLocation] {
+        &self.favorites
+    }
+
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        self.favorites.iter().any(|fav| fav.matches(lat, lon))
+    }
+
+    /// False when the place was already a favorite. A failed save keeps the change in
+    /// memory for this session.
+    pub fn add(&mut self, location: FavoriteLocation) -> Result<bool, FavoritesError> {
+        if self.contains(location.lat, location.lon) {
+            return Ok(false);
+        }
+
+        self.favorites.push(location);
+        self.save().map(|_| true)
+    }
+
+    /// False when the place wasn't a favorite
+    pub fn remove(&mut self, lat: f64, lon: f64) -> Result<bool, FavoritesError> {
+        let before = self.favorites.len();
+        self.favorites.retain(|fav| !fav.matches(lat, lon));
+        if self.favorites.len() == before {
+            return Ok(false);
+        }
+
+        self.save().map(|_| true)
+    }
+
+    /// Renames and retags a favorite. An empty name goes back to the one the provider
+    /// gave; tags are trimmed, and empty or repeated ones dropped.
+    pub fn edit(
+        &mut self,
+        index: usize,
+        name: &str,
+        tags: &[&str],
+    ) -> Result<bool, FavoritesError> {
+        let Some(favorite) = self.favorites.get_mut(index) else {
+            return Ok(false);
+        };
+
+        let name = name.trim();
+        favorite.display_name =
+            (!name.is_empty() && name != favorite.address).then(|| name.to_string());
+
+        favorite.tags.clear();
+        for tag in tags.iter().map(|tag| tag.trim()) {
+            if !tag.is_empty() && !favorite.has_tag(tag) {
+                favorite.tags.push(tag.to_string());
+            }
+        }
+        self.save().map(|_| true)
+    }
+
+    /// Moves a favorite one place up (`up`) or down, past the nearest favorite that is
+    /// in `group` too, so reordering a filtered list does what it looks like
+    pub fn move_favorite(
+        &mut self,
+        index: usize,
+        up: bool,
+        group: Option<&str>,
+    ) -> Result<bool, FavoritesError> {
+        if index >= self.favorites.len() {
+            return Ok(false);
+        }
+
+        let in_group = |fav: &FavoriteLocation| group.is_none_or(|group| fav.has_tag(group));
+        let target = if up {
+            (0..index).rev().find(|&i| in_group(&self.favorites[i]))
+        } else {
+            (index + 1..self.favorites.len()).find(|&i| in_group(&self.favorites[i]))
+        };
+        let Some(target) = target else {
+            return Ok(false);
+        };
+
+        let favorite = self.favorites.remove(index);
+        self.favorites.insert(target, favorite);
+        self.save().map(|_| true)
+    }
+
+    /// Sorts places from an import into new ones and ones that are already saved,
+    /// dropping repeats within the import itself
+    pub fn preview_import(&self, incoming: Vec<FavoriteLocation>) -> ImportPreview {
+        let mut preview = ImportPreview::default();
+        for favorite in incoming {
+            let seen = preview
+                .new
+                .iter()
+                .chain(preview.duplicates.iter().map(|(fav, _)| fav))
+                .any(|fav| fav.matches(favorite.lat, favorite.lon));
+            if seen {
+                continue;
+            }
+
+            match self
+                .favorites
+                .iter()
+                .position(|fav| fav.matches(favorite.lat, favorite.lon))
+            {
+                Some(index) => preview.duplicates.push((favorite, index)),
+                None => preview.new.push(favorite),
+            }
+        }
+        preview
+    }
+
+    /// Adds the new places from a preview, and with `update_duplicates` also takes the
+    /// names and groups of the ones already saved. Returns how many favorites changed.
+    pub fn import(
+        &mut self,
+        preview: ImportPreview,
+        update_duplicates: bool,
+    ) -> Result<usize, FavoritesError> {
+        let mut changed = 0;
+
+        if update_duplicates {
+            for (incoming, index) in preview.duplicates {
+                let Some(favorite) = self.favorites.get_mut(index) else {
+                    continue;
+                };
+                let before = favorite.clone();
+                if incoming.display_name.is_some() {
+                    favorite.display_name = incoming.display_name;
+                }
+                for tag in incoming.tags {
+                    if !favorite.has_tag(&tag) {
+                        favorite.tags.push(tag);
+                    }
+                }
+                if *favorite != before {
+                    changed += 1;
+                }
+            }
+        }
+
+        changed += preview.new.len();
+        self.favorites.extend(preview.new);
+
+        if changed == 0 {
+            return Ok(0);
+        }
+        self.save().map(|_| changed)
+    }
+
+    fn save(&mut self) -> Result<(), FavoritesError> {
+        let Some(path) = &self.path else {
+            return Err(FavoritesError::Unsaved);
+        };
+        let write_error = |source| FavoritesError::Write {
+            path: path.clone(),
+            source,
+        };
+
+        let file = FavoritesFile {
+            version: SCHEMA_VERSION,
+            favorites: self.favorites.clone(),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(|e| write_error(e.into()))?;
+
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        // a missing backup isn't worth losing the change over, so these only log
+        if let Some(version) = self.migrated_from.take() {
+            let old = sibling_path(path, &format!("v{version}"));
+            if let Err(e) = fs::copy(path, &old) {
+                eprintln!(
+                    "Failed to keep {} as {}: {e}",
+                    path.display(),
+                    old.display()
+                );
+            }
+        }
+        if let Err(e) = rotate_backups(path) {
+            eprintln!("Failed to back up {}: {e}", path.display());
+        }
+        write_atomically(path, json.as_bytes()).map_err(write_error)
+    }
+}
+
+/// What importing a file would do, see `FavoritesStore::preview_import`
+#[derive(Debug, Clone, Default)]
+pub struct ImportPreview {
+    pub new: Vec<FavoriteLocation>,
+    /// Places that are already favorites, with the index of the saved one
+    pub duplicates: Vec<(FavoriteLocation, usize)>,
+}
+
+/// Every tag in use, sorted, each once whatever its case
+pub fn groups(favorites: &[FavoriteLocation]) -> Vec<String> {
+    let mut groups: Vec<String> = Vec::new();
+    for tag in favorites.iter().flat_map(|fav| &fav.tags) {
+        if !groups.iter().any(|group| group.eq_ignore_ascii_case(tag)) {
+            groups.push(tag.clone());
+        }
+    }
+    groups.sort_by_key(|group| group.to_lowercase());
+    groups
+}
+
+// Brings older layouts up to SCHEMA_VERSION one step at a time. Also returns the
+// version the file started at.
+fn migrate(mut value: Value, path: &Path) -> Result<(FavoritesFile, u64), FavoritesError> {
+    let corrupt = |reason: String| FavoritesError::Corrupt {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let mut original = None;
+    loop {
+        let version = match &value {
+            // before versioning the file was a bare list
+            Value::Array(_) => 0,
+            Value::Object(file) => file
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| corrupt("no schema version".to_string()))?,
+            _ => return Err(corrupt("not a list of favorites".to_string())),
+        };
+
+        let started_at = *original.get_or_insert(version);
+
+        value = match version {
+            0 => json!({ "version": 1, "favorites": value }),
+            // display names and tags, which older versions would drop when saving
+            1 => {
+                value["version"] = json!(2);
+                value
+            }
+            SCHEMA_VERSION => {
+                let file = serde_json::from_value(value).map_err(|e| corrupt(e.to_string()))?;
+                return Ok((file, started_at));
+            }
+            version => {
+                return Err(FavoritesError::NewerVersion {
+                    path: path.to_path_buf(),
+                    version,
+                });
+            }
+        };
+    }
+}
+
+fn import_legacy(legacy: &Path, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomically(path, &fs::read(legacy)?)
+}
+
+// favorites.json -> favorites.json.1 -> favorites.json.2 ...
+fn rotate_backups(path: &Path) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    for n in (1..BACKUPS).rev() {
+        let older = sibling_path(path, &n.to_string());
+        if older.exists() {
+            fs::rename(&older, sibling_path(path, &(n + 1).to_string()))?;
+        }
+    }
+    fs::copy(path, sibling_path(path, "1")).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn store_path() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FILE_NAME);
+        (dir, path)
+    }
+
+    fn favorite(address: &str, lat: f64) -> FavoriteLocation {
+        FavoriteLocation::new(address.to_string(), lat, 27.6)
+    }
+
+    fn addresses(store: &FavoritesStore) -> Vec<&str> {
+        store
+            .favorites()
+            .iter()
+            .map(|fav| fav.address.as_str())
+            .collect()
+    }
+
+    fn saved_version(path: &Path) -> u64 {
+        let value: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        value["version"].as_u64().unwrap()
+    }
+
+    #[test]
+    fn saved_favorites_open_again() {
+        let (_dir, path) = store_path();
+
+        let mut store = FavoritesStore::open(path.clone()).unwrap();
+        assert!(store.favorites().is_empty());
+        assert!(store.add(favorite("Iasi", 47.16)).unwrap());
+        assert!(!store.add(favorite("Iași, RO", 47.161)).unwrap());
+        assert!(store.add(favorite("Suceava", 47.65)).unwrap());
+        store.edit(1, "Home", &["family", " ", "Family"]).unwrap();
+
+        let reopened = FavoritesStore::open(path.clone()).unwrap();
+        assert_eq!(reopened.favorites(), store.favorites());
+        assert_eq!(reopened.favorites()[1].name(), "Home");
+        assert_eq!(reopened.favorites()[1].tags, ["family"]);
+        assert_eq!(saved_version(&path), SCHEMA_VERSION);
+        // the temporary file was renamed into place
+        assert!(!sibling_path(&path, "tmp").exists());
+    }
+
+    #[test]
+    fn saves_keep_the_last_three_versions() {
+        let (_dir, path) = store_path();
+        let mut store = FavoritesStore::open(path.clone()).unwrap();
+
+        for (i, address) in ["A", "B", "C", "D", "E"].into_iter().enumerate() {
+            store.add(favorite(address, i as f64)).unwrap();
+        }
+
+        let backup = |n: &str| FavoritesStore::open(sibling_path(&path, n));
+        assert_eq!(addresses(&backup("1").unwrap()), ["A", "B", "C", "D"]);
+        assert_eq!(addresses(&backup("2").unwrap()), ["A", "B", "C"]);
+        assert_eq!(addresses(&backup("3").unwrap()), ["A", "B"]);
+        assert!(!sibling_path(&path, "4").exists());
+    }
+
+    #[test]
+    fn corrupt_file_is_left_byte_for_byte_intact() {
+        let (_dir, path) = store_path();
+        let damaged = b"{\"version\": 2, \"favorites\": [{\"address\": \"Ia";
+        fs::write(&path, damaged).unwrap();
+
+        assert!(matches!(
+            FavoritesStore::open(path.clone()),
+            Err(FavoritesError::Corrupt { .. })
+        ));
+
+        // what the app falls back to, which keeps changes in memory only
+        let mut store = FavoritesStore::unsaved();
+        assert!(matches!(
+            store.add(favorite("Iasi", 47.16)),
+            Err(FavoritesError::Unsaved)
+        ));
+        assert_eq!(store.favorites().len(), 1);
+
+        assert_eq!(fs::read(&path).unwrap(), damaged);
+        assert!(!sibling_path(&path, "1").exists());
+    }
+
+    #[test]
+    fn file_from_a_newer_version_is_refused() {
+        let (_dir, path) = store_path();
+        let newer = r#"{"version": 99, "favorites": [], "folders": []}"#;
+        fs::write(&path, newer).unwrap();
+
+        assert!(matches!(
+            FavoritesStore::open(path.clone()),
+            Err(FavoritesError::NewerVersion { version: 99, .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+    }
+
+    #[test]
+    fn file_without_a_version_is_refused() {
+        let (_dir, path) = store_path();
+        fs::write(&path, r#"{"favorites": []}"#).unwrap();
+
+        assert!(matches!(
+            FavoritesStore::open(path),
+            Err(FavoritesError::Corrupt { .. })
+        ));
+    }
+
+    #[test]
+    fn bare_list_is_migrated_and_the_original_kept() {
+        let (_dir, path) = store_path();
+        let v0 = r#"[{"address": "Iasi", "lat": 47.16, "lon": 27.58}]"#;
+        fs::write(&path, v0).unwrap();
+
+        let mut store = FavoritesStore::open(path.clone()).unwrap();
+        assert_eq!(addresses(&store), ["Iasi"]);
+        // nothing is written until something changes
+        assert_eq!(fs::read_to_string(&path).unwrap(), v0);
+
+        store.add(favorite("Suceava", 47.65)).unwrap();
+        assert_eq!(saved_version(&path), SCHEMA_VERSION);
+        assert_eq!(fs::read_to_string(sibling_path(&path, "v0")).unwrap(), v0);
+        assert_eq!(
+            addresses(&FavoritesStore::open(path.clone()).unwrap()),
+            ["Iasi", "Suceava"]
+        );
+
+        // only the first save after the migration copies it aside
+        fs::remove_file(sibling_path(&path, "v0")).unwrap();
+        store.remove(47.65, 27.6).unwrap();
+        assert!(!sibling_path(&path, "v0").exists());
+    }
+
+    #[test]
+    fn version_one_is_migrated() {
+        let (_dir, path) = store_path();
+        let v1 =
+            r#"{"version": 1, "favorites": [{"address": "Iasi", "lat": 47.16, "lon": 27.58}]}"#;
+        fs::write(&path, v1).unwrap();
+
+        let mut store = FavoritesStore::open(path.clone()).unwrap();
+        store.edit(0, "Home", &[]).unwrap();
+
+        assert_eq!(saved_version(&path), SCHEMA_VERSION);
+        assert_eq!(fs::read_to_string(sibling_path(&path, "v1")).unwrap(), v1);
+        assert_eq!(
+            FavoritesStore::open(path).unwrap().favorites()[0].name(),
+            "Home"
+        );
+    }
+
+    #[test]
+    fn moving_within_a_group_skips_other_favorites() {
+        let (_dir, path) = store_path();
+        let mut store = FavoritesStore::open(path).unwrap();
+        for (i, address) in ["A", "B", "C"].into_iter().enumerate() {
+            store.add(favorite(address, i as f64)).unwrap();
+        }
+        store.edit(0, "", &["ski"]).unwrap();
+        store.edit(2, "", &["Ski"]).unwrap();
+
+        assert!(store.move_favorite(2, true, Some("ski")).unwrap());
+        assert_eq!(addresses(&store), ["C", "A", "B"]);
+        assert!(!store.move_favorite(0, true, None).unwrap());
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+
+pub fn capitalize_first(string: String) -> String {
+    let mut result = String::new();
+    let mut first = true;
+    for char in string.chars() {
+        if first {
+            first = false;
+            result.push(char.to_ascii_uppercase());
+        } else {
+            result.push(char);
+            if char == ' ' {
+                first = true;
+            }
+        }
+    }
+
+    result
+}
+
+/// Quotes a CSV field when it needs it (RFC 4180), doubling any quotes inside
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn date_to_day(date: String) -> String {
+    let format = "%Y-%m-%d";
+
+    let naive_date = match NaiveDate::parse_from_str(date.as_str(), format) {
+        Ok(date) => date,
+        Err(e) => {
+            eprintln!("Error parsing string to date: {e}");
+            return "Invalid Date".to_string();
+        }
+    };
+
+    let weekday = naive_date.weekday();
+
+    weekday.to_string()
+}
+
+/// 230° -> "SW"
+pub fn compass_point(degrees: f32) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+    let index = (degrees.rem_euclid(360.0) / 45.0).round() as usize % POINTS.len();
+    POINTS[index]
+}
+
+/// 38_880 -> "10h 48m"
+pub fn hours_minutes(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// 133 -> "+2m 13s", -161 -> "-2m 41s"
+pub fn signed_minutes_seconds(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    format!("{sign}{}m {:02}s", seconds / 60, seconds % 60)
+}
+
+/// "07:22:13" -> "07:22"
+pub fn short_time(time: &str) -> String {
+    time.get(..5).unwrap_or(time).to_string()
+}
+
+/// "just now", "12 min ago", "3 h ago", or the day and time when it's been over a day
+pub fn time_ago(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let minutes = (now - time).num_minutes();
+    match minutes {
+        ..1 => "just now".to_string(),
+        1..60 => format!("{minutes} min ago"),
+        60..1440 => format!("{} h ago", minutes / 60),
+        _ => time.with_timezone(&Local).format("on %a %H:%M").to_string(),
+    }
+}
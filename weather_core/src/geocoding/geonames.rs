@@ -0,0 +1,389 @@
+use super::{Geocoder, Place};
+use crate::config::Config;
+use crate::error::WeatherError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Offline place search over a GeoNames city dump such as cities15000.txt
+// (https://download.geonames.org/export/dump/). Tab-separated, one city per line.
+// admin1CodesASCII.txt and countryInfo.txt next to it turn region and country codes
+// into names; without them the codes are shown.
+
+pub const DEFAULT_FILE_NAME: &str = "cities15000.txt";
+const ADMIN1_FILE_NAME: &str = "admin1CodesASCII.txt";
+const COUNTRIES_FILE_NAME: &str = "countryInfo.txt";
+
+/// How far a point may be from a city and still be named after it
+pub const NEAREST_MAX_KM: f64 = 30.0;
+/// How close a provider's point must be to a city to be that city, not a place near it
+pub const SAME_PLACE_KM: f64 = 3.0;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+// roughly, along a meridian
+const KM_PER_DEGREE: f64 = 111.2;
+
+// column numbers in the GeoNames "geoname" table
+const COL_NAME: usize = 1;
+const COL_ASCII_NAME: usize = 2;
+const COL_LAT: usize = 4;
+const COL_LON: usize = 5;
+const COL_COUNTRY: usize = 8;
+const COL_ADMIN1: usize = 10;
+const COL_POPULATION: usize = 14;
+
+struct City {
+    place: Place,
+    /// GeoNames' plain ASCII spelling, so "iasi" finds "Iași"
+    ascii_name: String,
+    country_code: String,
+    population: u64,
+}
+
+/// Every city in memory, with a sorted name list for prefix search and a 1°x1° grid
+/// for nearest-city lookups
+pub struct CityIndex {
+    cities: Vec<City>,
+    /// Lowercased name and ASCII name of every city, sorted
+    names: Vec<(String, usize)>,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl CityIndex {
+    /// cities15000.txt in the data dir, used unless the config names a file
+    pub fn default_path(config: &Config) -> Option<PathBuf> {
+        config.data_path(DEFAULT_FILE_NAME)
+    }
+
+    /// The configured city file, None when there's none. A missing file at the default
+    /// location is normal; anything else wrong with it is logged.
+    pub fn from_config(config: &Config) -> Option<Arc<Self>> {
+        let path = match &config.cities_file {
+            Some(path) => path.clone(),
+            None => Self::default_path(config).filter(|path| path.exists())?,
+        };
+
+        match Self::load(&path) {
+            Ok(index) => {
+                eprintln!("Loaded {} cities from {}", index.len(), path.display());
+                Some(Arc::new(index))
+            }
+            Err(e) => {
+                eprintln!("Failed to load cities from {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let content = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let regions = read_code_names(&dir.join(ADMIN1_FILE_NAME), 0, 1);
+        let countries = read_code_names(&dir.join(COUNTRIES_FILE_NAME), 0, 4);
+
+        let cities = content
+            .lines()
+            .filter_map(|line| parse_city(line, &regions, &countries))
+            .collect::<Vec<_>>();
+        if cities.is_empty() {
+            return Err(anyhow::anyhow!(
+                "no cities in the file, is it a GeoNames dump?"
+            ));
+        }
+
+        Ok(Self::new(cities))
+    }
+
+    fn new(cities: Vec<City>) -> Self {
+        let mut names = Vec::with_capacity(cities.len() * 2);
+        let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+
+        for (i, city) in cities.iter().enumerate() {
+            let name = city.place.name.to_lowercase();
+            let ascii_name = city.ascii_name.to_lowercase();
+            if ascii_name != name {
+                names.push((ascii_name, i));
+            }
+            names.push((name, i));
+
+            grid.entry(cell(city.place.lat, city.place.lon))
+                .or_default()
+                .push(i);
+        }
+        names.sort();
+
+        Self {
+            cities,
+            names,
+            grid,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cities.is_empty()
+    }
+
+    /// "Paris" or "Paris, US" / "Paris, Texas": exact names before prefixes, then the
+    /// biggest cities first. Anything after a comma must start a region, country or
+    /// country code.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<Place> {
+        self.find(query)
+            .into_iter()
+            .take(limit)
+            .map(|(_, i)| self.cities[i].place.clone())
+            .collect()
+    }
+
+    /// The biggest city named exactly `query` ("Paris, Texas" works too), ignoring
+    /// cities that only start with it
+    pub fn find_exact(&self, query: &str) -> Option<Place> {
+        self.find(query)
+            .into_iter()
+            .find(|(exact, _)| *exact)
+            .map(|(_, i)| self.cities[i].place.clone())
+    }
+
+    // (exact name, city index) for every city whose name starts with the query, sorted
+    // the way `search` returns them
+    fn find(&self, query: &str) -> Vec<(bool, usize)> {
+        let mut parts = query.split(',').map(|part| part.trim().to_lowercase());
+        let Some(name) = parts.next().filter(|name| !name.is_empty()) else {
+            return Vec::new();
+        };
+        let qualifiers: Vec<String> = parts.filter(|part| !part.is_empty()).collect();
+
+        let start = self
+            .names
+            .partition_point(|(key, _)| key.as_str() < name.as_str());
+        let mut found: Vec<(bool, usize)> = Vec::new();
+        for (key, i) in self.names[start..]
+            .iter()
+            .take_while(|(key, _)| key.starts_with(&name))
+        {
+            let exact = *key == name;
+            match found.iter_mut().find(|(_, seen)| seen == i) {
+                Some(entry) => entry.0 |= exact,
+                None => found.push((exact, *i)),
+            }
+        }
+
+        found.retain(|(_, i)| {
+            let city = &self.cities[*i];
+            qualifiers.iter().all(|qualifier| city.matches(qualifier))
+        });
+        found.sort_by(|(a_exact, a), (b_exact, b)| {
+            b_exact
+                .cmp(a_exact)
+                .then(self.cities[*b].population.cmp(&self.cities[*a].population))
+        });
+        found
+    }
+
+    /// The closest city within `max_km`
+    pub fn nearest(&self, lat: f64, lon: f64, max_km: f64) -> Option<Place> {
+        let (row, col) = cell(lat, lon);
+        let rows = (max_km / KM_PER_DEGREE).ceil() as i32;
+        // meridians get closer towards the poles, so more columns cover the same distance
+        let shrink = lat.to_radians().cos().max(0.01);
+        let cols = ((max_km / (KM_PER_DEGREE * shrink)).ceil() as i32).min(180);
+
+        let mut best: Option<(f64, usize)> = None;
+        for r in row - rows..=row + rows {
+            for c in col - cols..=col + cols {
+                let Some(indices) = self.grid.get(&(r, (c + 180).rem_euclid(360) - 180)) else {
+                    continue;
+                };
+                for &i in indices {
+                    let place = &self.cities[i].place;
+                    let distance = distance_km(lat, lon, place.lat, place.lon);
+                    if distance <= max_km && best.is_none_or(|(closest, _)| distance < closest) {
+                        best = Some((distance, i));
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, i)| self.cities[i].place.clone())
+    }
+
+    /// A readable name for a point, e.g. a saved favorite, when a city is close enough
+    pub fn name_for(&self, lat: f64, lon: f64) -> Option<String> {
+        self.nearest(lat, lon, NEAREST_MAX_KM)
+            .map(|place| place.display_name())
+    }
+
+    /// The city a point is in, when it's practically on top of one. Unlike `name_for`
+    /// this won't call a village after the town next to it.
+    pub fn city_at(&self, lat: f64, lon: f64) -> Option<Place> {
+        self.nearest(lat, lon, SAME_PLACE_KM)
+    }
+}
+
+impl City {
+    fn matches(&self, qualifier: &str) -> bool {
+        [
+            self.place.region.as_deref(),
+            self.place.country.as_deref(),
+            Some(self.country_code.as_str()),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|field| field.to_lowercase().starts_with(qualifier))
+    }
+}
+
+#[async_trait]
+impl Geocoder for CityIndex {
+    fn name(&self) -> &'static str {
+        "geonames"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<Place>, WeatherError> {
+        Ok(CityIndex::search(self, query, limit))
+    }
+}
+
+fn parse_city(
+    line: &str,
+    regions: &HashMap<String, String>,
+    countries: &HashMap<String, String>,
+) -> Option<City> {
+    let columns: Vec<&str> = line.split('\t').collect();
+    let column = |i: usize| columns.get(i).map(|value| value.trim());
+
+    let name = column(COL_NAME).filter(|name| !name.is_empty())?;
+    let lat: f64 = column(COL_LAT)?.parse().ok()?;
+    let lon: f64 = column(COL_LON)?.parse().ok()?;
+    let country_code = column(COL_COUNTRY).unwrap_or_default();
+    let admin1 = column(COL_ADMIN1).unwrap_or_default();
+
+    let region = regions.get(&format!("{country_code}.{admin1}")).cloned();
+    let country = countries
+        .get(country_code)
+        .cloned()
+        .or_else(|| (!country_code.is_empty()).then(|| country_code.to_string()));
+
+    Some(City {
+        place: Place {
+            name: name.to_string(),
+            region,
+            country,
+            lat,
+            lon,
+        },
+        ascii_name: column(COL_ASCII_NAME).unwrap_or(name).to_string(),
+        country_code: country_code.to_lowercase(),
+        population: column(COL_POPULATION)
+            .and_then(|population| population.parse().ok())
+            .unwrap_or(0),
+    })
+}
+
+// "RO.23\tIasi\t..." -> {"RO.23": "Iasi"}. Missing files give an empty map.
+fn read_code_names(path: &Path, code_column: usize, name_column: usize) -> HashMap<String, String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            Some((
+                columns.get(code_column)?.to_string(),
+                columns.get(name_column)?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+fn cell(lat: f64, lon: f64) -> (i32, i32) {
+    (lat.floor() as i32, lon.floor() as i32)
+}
+
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // a few rows in the cities15000.txt layout, without the region and country files
+    const CITIES: &str = "\
+683506\tBrașov\tBrasov\t\t45.64861\t25.60613\tP\tPPLA\tRO\t\t04\t\t\t\t253200
+2945024\tBrandenburg an der Havel\tBrandenburg an der Havel\t\t52.41667\t12.55\tP\tPPLA3\tDE\t\t11\t\t\t\t71886
+675810\tIași\tIasi\t\t47.16667\t27.6\tP\tPPLA\tRO\t\t23\t\t\t\t318012
+2988507\tParis\tParis\t\t48.85341\t2.3488\tP\tPPLC\tFR\t\t11\t\t\t\t2138551
+4717560\tParis\tParis\t\t33.66094\t-95.55551\tP\tPPLA2\tUS\t\tTX\t\t\t\t24782
+";
+
+    pub(crate) fn index() -> CityIndex {
+        let none = HashMap::new();
+        CityIndex::new(
+            CITIES
+                .lines()
+                .filter_map(|line| parse_city(line, &none, &none))
+                .collect(),
+        )
+    }
+
+    fn names(places: &[Place]) -> Vec<&str> {
+        places.iter().map(|place| place.name.as_str()).collect()
+    }
+
+    #[test]
+    fn search_includes_prefixes_but_find_exact_does_not() {
+        let index = index();
+
+        assert_eq!(
+            names(&index.search("bra", 5)),
+            ["Brașov", "Brandenburg an der Havel"]
+        );
+        assert_eq!(index.find_exact("Bra"), None);
+        assert_eq!(
+            index.find_exact("brasov").map(|place| place.name),
+            Some("Brașov".to_string())
+        );
+    }
+
+    #[test]
+    fn exact_names_prefer_big_cities_unless_qualified() {
+        let index = index();
+
+        let paris = index.find_exact("Paris").unwrap();
+        assert_eq!(paris.country.as_deref(), Some("FR"));
+        let texas = index.find_exact("paris, us").unwrap();
+        assert_eq!(texas.lat, 33.66094);
+        assert_eq!(index.find_exact("Paris, DE"), None);
+    }
+
+    #[test]
+    fn city_at_only_names_points_inside_a_city() {
+        let index = index();
+
+        // ~1.3 km from the center of Iași
+        assert_eq!(
+            index.city_at(47.17, 27.615).map(|place| place.name),
+            Some("Iași".to_string())
+        );
+
+        // Bran, a village 25 km from Brașov
+        assert_eq!(index.city_at(45.515, 25.367), None);
+        assert_eq!(
+            index.name_for(45.515, 25.367),
+            Some("Brașov, RO".to_string())
+        );
+    }
+}
@@ -0,0 +1,98 @@
+mod geonames;
+mod open_meteo;
+
+pub use geonames::CityIndex;
+#[cfg(test)]
+pub(crate) use geonames::tests as geonames_tests;
+pub use open_meteo::OpenMeteoGeocoder;
+
+use crate::cache::ResponseCache;
+use crate::config::Config;
+use crate::error::WeatherError;
+use crate::retry::RetryPolicy;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// A named place with the point the forecast should be fetched for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub name: String,
+    /// State, county or similar, when the backend knows it
+    pub region: Option<String>,
+    pub country: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Place {
+    /// "Moscow, Idaho, United States"
+    pub fn display_name(&self) -> String {
+        [
+            Some(&self.name),
+            self.region.as_ref(),
+            self.country.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+/// Turns what the user typed into places to pick from
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Best matches first, at most `limit` of them. No match is an empty list, not an error.
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<Place>, WeatherError>;
+}
+
+/// Asks the local city list first and only goes to the network when it has nothing
+pub struct LocalFirst {
+    local: Arc<CityIndex>,
+    remote: Box<dyn Geocoder>,
+}
+
+impl LocalFirst {
+    pub fn new(local: Arc<CityIndex>, remote: Box<dyn Geocoder>) -> Self {
+        Self { local, remote }
+    }
+}
+
+#[async_trait]
+impl Geocoder for LocalFirst {
+    fn name(&self) -> &'static str {
+        "local-first"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<Place>, WeatherError> {
+        let places = self.local.search(query, limit);
+        if !places.is_empty() {
+            return Ok(places);
+        }
+        self.remote.search(query, limit).await
+    }
+}
+
+/// Builds the geocoder used for search suggestions, preferring `cities` when there is one
+pub fn from_config(
+    config: &Config,
+    client: &reqwest::Client,
+    cities: Option<Arc<CityIndex>>,
+) -> Box<dyn Geocoder> {
+    let mut geocoder = OpenMeteoGeocoder::new(client.clone(), &config.lang)
+        .with_retry(RetryPolicy::from_config(config));
+    if let Some(url) = &config.open_meteo_url {
+        geocoder = geocoder.with_base_url(url);
+    }
+    if let Some(cache) = ResponseCache::from_config(config) {
+        geocoder = geocoder.with_cache(cache);
+    }
+
+    match cities {
+        Some(cities) => Box::new(LocalFirst::new(cities, Box::new(geocoder))),
+        None => Box::new(geocoder),
+    }
+}
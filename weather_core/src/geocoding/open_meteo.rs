@@ -0,0 +1,112 @@
+use super::{Geocoder, Place};
+use crate::cache::ResponseCache;
+use crate::error::WeatherError;
+use crate::providers::get_text;
+use crate::retry::{CircuitBreaker, RetryPolicy};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com";
+// the API refuses anything above this
+const MAX_RESULTS: usize = 100;
+
+/// Keyless place search from open-meteo.com, backed by GeoNames
+pub struct OpenMeteoGeocoder {
+    url: String,
+    lang: String,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+    cache: Option<Arc<ResponseCache>>,
+}
+
+impl OpenMeteoGeocoder {
+    pub fn new(client: reqwest::Client, lang: impl Into<String>) -> Self {
+        Self {
+            url: GEOCODING_URL.to_string(),
+            lang: lang.into(),
+            client,
+            retry: RetryPolicy::default(),
+            breaker: CircuitBreaker::new("open-meteo-geocoding"),
+            cache: None,
+        }
+    }
+
+    pub fn with_base_url(self, base_url: impl Into<String>) -> Self {
+        Self {
+            url: base_url.into(),
+            ..self
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+
+    pub fn with_cache(self, cache: Arc<ResponseCache>) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for OpenMeteoGeocoder {
+    fn name(&self) -> &'static str {
+        "open-meteo-geocoding"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<Place>, WeatherError> {
+        let count = limit.clamp(1, MAX_RESULTS).to_string();
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/v1/search", self.url),
+            &[
+                ("name", query),
+                ("count", count.as_str()),
+                ("language", self.lang.as_str()),
+            ],
+        )
+        .map_err(|e| WeatherError::Network(format!("invalid geocoding URL: {e}")))?;
+
+        let cache_key = format!("{}|{url}", self.name());
+        let response = get_text(
+            &self.client,
+            &self.retry,
+            &self.breaker,
+            self.cache.as_deref(),
+            &cache_key,
+            url.as_str(),
+        )
+        .await?;
+        let response: OmGeocodingResponse = serde_json::from_str(&response.body)?;
+
+        Ok(response
+            .results
+            .into_iter()
+            .map(|place| Place {
+                name: place.name,
+                region: place.admin1,
+                country: place.country,
+                lat: place.latitude,
+                lon: place.longitude,
+            })
+            .collect())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct OmGeocodingResponse {
+    #[serde(default)]
+    results: Vec<OmPlace>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmPlace {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    admin1: Option<String>,
+    country: Option<String>,
+}
@@ -0,0 +1,195 @@
+use crate::config::Config;
+use crate::paths;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecentSearch {
+    /// The name the forecast was shown under
+    pub address: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub searched_at: DateTime<Utc>,
+}
+
+impl RecentSearch {
+    fn matches(&self, lat: f64, lon: f64) -> bool {
+        const EPSILON: f64 = 0.01;
+        (self.lat - lat).abs() < EPSILON && (self.lon - lon).abs() < EPSILON
+    }
+}
+
+/// Successful searches, most recent first, at most `capacity` of them
+#[derive(Debug, Clone, Default)]
+pub struct SearchHistory {
+    entries: Vec<RecentSearch>,
+    capacity: usize,
+    /// Where it's saved, None keeps it in memory only
+    path: Option<PathBuf>,
+}
+
+impl SearchHistory {
+    pub fn new(capacity: usize) -> Self {
+        SearchHistory {
+            entries: Vec::new(),
+            capacity,
+            path: None,
+        }
+    }
+
+    /// history.json from the data dir, or an empty history when there is none or it
+    /// can't be read
+    pub fn from_config(config: &Config) -> Self {
+        let mut history = SearchHistory::new(config.history_size);
+        let Some(path) = config.data_path("history.json") else {
+            return history;
+        };
+
+        // a missing file just means nothing was searched yet
+        if let Ok(content) = fs::read_to_string(&path) {
+            match serde_json::from_str::<Vec<RecentSearch>>(&content) {
+                Ok(entries) => {
+                    history.entries = entries;
+                    history.entries.truncate(config.history_size);
+                }
+                Err(e) => eprintln!("Error parsing {} ({e}), ignoring it", path.display()),
+            }
+        }
+        history.path = Some(path);
+        history
+    }
+
+    pub fn entries(&self) -> &[RecentSearch] {
+        &self.entries
+    }
+
+    /// Puts the search at the top, replacing an earlier search for the same place
+    pub fn record(&mut self, address: String, lat: f64, lon: f64, now: DateTime<Utc>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.entries.retain(|entry| !entry.matches(lat, lon));
+        self.entries.insert(
+            0,
+            RecentSearch {
+                address,
+                lat,
+                lon,
+                searched_at: now,
+            },
+        );
+        self.entries.truncate(self.capacity);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        if let Some(dir) = path.parent()
+            && let Err(e) = fs::create_dir_all(dir)
+        {
+            eprintln!("Failed to create {}: {e}", dir.display());
+            return;
+        }
+
+        match serde_json::to_string_pretty(&self.entries) {
+            Ok(json) => {
+                if let Err(e) = paths::write_atomically(path, json.as_bytes()) {
+                    eprintln!("Failed to write {}: {e}", path.display());
+                }
+            }
+            Err(e) => eprintln!("Error serializing search history: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn addresses(history: &SearchHistory) -> Vec<&str> {
+        history
+            .entries()
+            .iter()
+            .map(|entry| entry.address.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn searching_a_place_again_moves_it_to_the_top() {
+        let now = Utc::now();
+        let mut history = SearchHistory::new(10);
+        history.record("Iasi".to_string(), 47.16, 27.59, now);
+        history.record("Cluj-Napoca".to_string(), 46.77, 23.6, now);
+        // the provider's coordinates wobble a little between searches
+        let later = now + Duration::minutes(5);
+        history.record("Iași, Romania".to_string(), 47.165, 27.585, later);
+
+        assert_eq!(addresses(&history), ["Iași, Romania", "Cluj-Napoca"]);
+        assert_eq!(history.entries()[0].searched_at, later);
+    }
+
+    #[test]
+    fn keeps_at_most_capacity_searches() {
+        let mut history = SearchHistory::new(3);
+        for (i, name) in ["A", "B", "C", "D", "E"].into_iter().enumerate() {
+            history.record(name.to_string(), i as f64, 0.0, Utc::now());
+        }
+        assert_eq!(addresses(&history), ["E", "D", "C"]);
+
+        let mut disabled = SearchHistory::new(0);
+        disabled.record("A".to_string(), 0.0, 0.0, Utc::now());
+        assert!(disabled.entries().is_empty());
+    }
+
+    #[test]
+    fn is_saved_in_the_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            data_dir: Some(dir.path().join("data")),
+            history_size: 5,
+            ..Config::default()
+        };
+
+        let mut history = SearchHistory::from_config(&config);
+        assert!(history.entries().is_empty());
+        for (i, name) in ["A", "B", "C"].into_iter().enumerate() {
+            history.record(name.to_string(), i as f64, 0.0, Utc::now());
+        }
+        history.save();
+
+        let loaded = SearchHistory::from_config(&config);
+        assert_eq!(loaded.entries(), history.entries());
+
+        // a smaller history_size drops the oldest ones
+        let smaller = Config {
+            history_size: 2,
+            ..config.clone()
+        };
+        assert_eq!(addresses(&SearchHistory::from_config(&smaller)), ["C", "B"]);
+
+        history.clear();
+        history.save();
+        assert!(SearchHistory::from_config(&config).entries().is_empty());
+    }
+
+    #[test]
+    fn unreadable_history_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("history.json"), "{ not json").unwrap();
+        let config = Config {
+            data_dir: Some(dir.path().to_path_buf()),
+            ..Config::default()
+        };
+        assert!(SearchHistory::from_config(&config).entries().is_empty());
+    }
+}
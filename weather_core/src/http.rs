@@ -0,0 +1,129 @@
+use crate::config::Config;
+use std::fs;
+use std::time::Duration;
+
+/// Sent with every request, so providers can tell who is calling
+pub const USER_AGENT: &str = concat!(
+    "rust-weather-app/",
+    env!("CARGO_PKG_VERSION"),
+    " (desktop weather app)"
+);
+
+const PROXY_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+/// The one client every provider and geocoder request goes through, so connections
+/// are reused. Responses are gzip-compressed when the server supports it.
+///
+/// A bad proxy URL or CA file is an error here, at startup, instead of every request
+/// failing later with a vague network error.
+pub fn client_from_config(config: &Config) -> Result<reqwest::Client, anyhow::Error> {
+    if config.connect_timeout_secs == 0 || config.read_timeout_secs == 0 {
+        return Err(anyhow::anyhow!(
+            "connect_timeout_secs and read_timeout_secs must be at least 1"
+        ));
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .read_timeout(Duration::from_secs(config.read_timeout_secs));
+
+    if let Some(proxy) = &config.proxy {
+        let url = reqwest::Url::parse(proxy)
+            .map_err(|e| anyhow::anyhow!("Invalid proxy '{proxy}': {e}"))?;
+        if !PROXY_SCHEMES.contains(&url.scheme()) || url.host_str().is_none() {
+            return Err(anyhow::anyhow!(
+                "Invalid proxy '{proxy}', expected http://, https://, socks5:// or socks5h://host:port"
+            ));
+        }
+        let proxy = reqwest::Proxy::all(url)
+            .map_err(|e| anyhow::anyhow!("Invalid proxy '{proxy}': {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &config.ca_file {
+        let pem = fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Can't read CA file {}: {e}", path.display()))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| anyhow::anyhow!("Invalid CA file {}: {e}", path.display()))?;
+        if certificates.is_empty() {
+            return Err(anyhow::anyhow!(
+                "CA file {} has no PEM certificates in it",
+                path.display()
+            ));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| anyhow::anyhow!("Can't set up the HTTP client: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(config: &Config) -> String {
+        client_from_config(config)
+            .expect_err("the config should be refused")
+            .to_string()
+    }
+
+    #[test]
+    fn default_config_builds_a_client() {
+        assert!(client_from_config(&Config::default()).is_ok());
+    }
+
+    #[test]
+    fn refuses_unusable_proxies() {
+        for proxy in ["ftp://proxy.example:21", "proxy.example:8080", "http://"] {
+            let config = Config {
+                proxy: Some(proxy.to_string()),
+                ..Config::default()
+            };
+            let message = error(&config);
+            assert!(message.starts_with("Invalid proxy"), "{proxy}: {message}");
+        }
+
+        let config = Config {
+            proxy: Some("socks5h://127.0.0.1:1080".to_string()),
+            ..Config::default()
+        };
+        assert!(client_from_config(&config).is_ok());
+    }
+
+    #[test]
+    fn refuses_missing_and_invalid_ca_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let with_ca = |name: &str| Config {
+            ca_file: Some(dir.path().join(name)),
+            ..Config::default()
+        };
+
+        assert!(error(&with_ca("missing.pem")).starts_with("Can't read CA file"));
+
+        fs::write(dir.path().join("empty.pem"), "not a certificate\n").unwrap();
+        let message = error(&with_ca("empty.pem"));
+        assert!(message.contains("has no PEM certificates"), "{message}");
+
+        fs::write(
+            dir.path().join("broken.pem"),
+            "-----BEGIN CERTIFICATE-----\nnot base64!\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        let message = error(&with_ca("broken.pem"));
+        assert!(message.starts_with("Invalid CA file"), "{message}");
+    }
+
+    #[test]
+    fn refuses_zero_timeouts() {
+        let config = Config {
+            read_timeout_secs: 0,
+            ..Config::default()
+        };
+        assert!(error(&config).contains("at least 1"));
+    }
+}
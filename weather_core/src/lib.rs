@@ -0,0 +1,25 @@
+//! Everything that isn't UI: forecast models, weather providers, place search and
+//! coordinate parsing, the response cache, configuration, the shared HTTP client, retries and circuit breaking, favorites and search
+//! history persistence, notification rules, the refresh schedule, sun/moon
+//! calculations and display formatting.
+
+pub mod astro;
+pub mod cache;
+pub mod config;
+pub mod coords;
+pub mod error;
+pub mod favorites;
+pub mod format;
+pub mod geocoding;
+pub mod history;
+pub mod http;
+pub mod paths;
+pub mod providers;
+pub mod query;
+pub mod refresh;
+pub mod retry;
+pub mod rules;
+#[cfg(test)]
+mod test_server;
+pub mod units;
+pub mod weather_structs;
@@ -0,0 +1,51 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const APP_DIR_NAME: &str = "rust-weather-app";
+
+/// `$XDG_CONFIG_HOME/rust-weather-app`, falling back to `~/.config/rust-weather-app`
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_CACHE_HOME/rust-weather-app`, falling back to `~/.cache/rust-weather-app`
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_DATA_HOME/rust-weather-app`, falling back to `~/.local/share/rust-weather-app`
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+fn xdg_dir(var: &str, home_fallback: &str) -> Option<PathBuf> {
+    let base = match env::var_os(var) {
+        // the spec says relative paths in these variables are invalid and must be ignored
+        Some(dir) if PathBuf::from(&dir).is_absolute() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(home_fallback),
+    };
+
+    Some(base.join(APP_DIR_NAME))
+}
+
+/// Replaces `path` with `contents` through a temporary file next to it, so a crash
+/// halfway leaves either the old file or the new one, never half of it
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = sibling_path(path, "tmp");
+    let mut tmp = File::create(&tmp_path)?;
+    tmp.write_all(contents)?;
+    tmp.sync_all()?;
+    drop(tmp);
+
+    fs::rename(&tmp_path, path)
+}
+
+/// `path` with `.suffix` added after its extension
+pub(crate) fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,89 @@
+use super::WeatherProvider;
+use crate::error::WeatherError;
+use crate::weather_structs::{CurrentConditions, ForecastResponse};
+use async_trait::async_trait;
+
+/// Asks `primary` first and only goes to `secondary` when it fails (quota exhausted, outage, ...)
+pub struct Fallback {
+    primary: Box<dyn WeatherProvider>,
+    secondary: Box<dyn WeatherProvider>,
+}
+
+impl Fallback {
+    pub fn new(primary: Box<dyn WeatherProvider>, secondary: Box<dyn WeatherProvider>) -> Self {
+        Self { primary, secondary }
+    }
+
+    fn log_fallback(&self, e: &WeatherError) {
+        eprintln!(
+            "{} failed ({e}), falling back to {}",
+            self.primary.name(),
+            self.secondary.name()
+        );
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Fallback {
+    fn name(&self) -> &'static str {
+        self.primary.name()
+    }
+
+    async fn forecast(&self, location: &str) -> Result<ForecastResponse, WeatherError> {
+        match self.primary.forecast(location).await {
+            Ok(forecast) => Ok(forecast),
+            Err(e) => {
+                self.log_fallback(&e);
+                self.secondary.forecast(location).await
+            }
+        }
+    }
+
+    async fn forecast_by_coords(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<ForecastResponse, WeatherError> {
+        match self.primary.forecast_by_coords(lat, lon).await {
+            Ok(forecast) => Ok(forecast),
+            Err(e) => {
+                self.log_fallback(&e);
+                self.secondary.forecast_by_coords(lat, lon).await
+            }
+        }
+    }
+
+    async fn current(&self, lat: f64, lon: f64) -> Result<CurrentConditions, WeatherError> {
+        match self.primary.current(lat, lon).await {
+            Ok(current) => Ok(current),
+            Err(e) => {
+                self.log_fallback(&e);
+                self.secondary.current(lat, lon).await
+            }
+        }
+    }
+
+    // only the places the primary couldn't answer go to the secondary, in one batch
+    async fn current_many(
+        &self,
+        points: &[(f64, f64)],
+    ) -> Vec<Result<CurrentConditions, WeatherError>> {
+        let mut results = self.primary.current_many(points).await;
+        let failed: Vec<usize> = (0..results.len())
+            .filter(|&i| results[i].is_err())
+            .collect();
+        let Some(Err(e)) = failed.first().map(|&i| &results[i]) else {
+            return results;
+        };
+        self.log_fallback(e);
+
+        let retried: Vec<(f64, f64)> = failed.iter().map(|&i| points[i]).collect();
+        for (i, result) in failed
+            .into_iter()
+            .zip(self.secondary.current_many(&retried).await)
+        {
+            results[i] = result;
+        }
+        results
+    }
+}
@@ -0,0 +1,139 @@
+mod fallback;
+mod open_meteo;
+mod visual_crossing;
+
+pub use fallback::Fallback;
+pub use open_meteo::OpenMeteo;
+pub use visual_crossing::VisualCrossing;
+
+use crate::cache::{CachedResponse, ResponseCache};
+use crate::config::{Config, ProviderKind};
+use crate::error::{self, WeatherError};
+use crate::retry::{CircuitBreaker, RetryPolicy};
+use crate::weather_structs::{CurrentConditions, ForecastResponse};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+
+/// Requests in flight at once when a provider can't ask for several places in one
+const MAX_CONCURRENT: usize = 4;
+
+#[async_trait]
+pub trait WeatherProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Multi-day forecast for a free-text location ("Iasi", "Paris, France", ...)
+    async fn forecast(&self, location: &str) -> Result<ForecastResponse, WeatherError>;
+
+    /// Same as `forecast`, but for an exact point
+    async fn forecast_by_coords(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<ForecastResponse, WeatherError>;
+
+    /// Today's snapshot (icon, temp, min/max) used by the favorites panel
+    async fn current(&self, lat: f64, lon: f64) -> Result<CurrentConditions, WeatherError>;
+
+    /// `current` for several places, one result per place in the same order. Providers
+    /// whose API takes many places in one request override this; the default asks for
+    /// a few at a time.
+    async fn current_many(
+        &self,
+        points: &[(f64, f64)],
+    ) -> Vec<Result<CurrentConditions, WeatherError>> {
+        stream::iter(points.iter().copied())
+            .map(|(lat, lon)| self.current(lat, lon))
+            .buffered(MAX_CONCURRENT)
+            .collect()
+            .await
+    }
+}
+
+/// GETs `url`, going through the response cache when the provider has one. Failures
+/// are retried as `retry` says, unless `breaker` has paused the provider.
+/// `key` identifies the request and must not contain secrets like API keys.
+pub(crate) async fn get_text(
+    client: &reqwest::Client,
+    retry: &RetryPolicy,
+    breaker: &CircuitBreaker,
+    cache: Option<&ResponseCache>,
+    key: &str,
+    url: &str,
+) -> Result<CachedResponse, WeatherError> {
+    let attempt = || async {
+        let response = client.get(url).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response.text().await?);
+        }
+
+        let retry_after = error::retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        Err(WeatherError::from_status(status, retry_after, &body))
+    };
+    let fetch = || retry.run(Some(breaker), key, attempt);
+
+    match cache {
+        Some(cache) => cache.get_or_fetch(key, fetch).await,
+        None => Ok(CachedResponse {
+            body: fetch().await?,
+            fetched_at: Utc::now(),
+            stale: false,
+        }),
+    }
+}
+
+/// Builds the provider chain described by `config`, all sharing `client`
+pub fn from_config(
+    config: &Config,
+    client: &reqwest::Client,
+) -> Result<Box<dyn WeatherProvider>, anyhow::Error> {
+    let cache = ResponseCache::from_config(config);
+    if cache.is_none() && config.offline {
+        return Err(anyhow::anyhow!(
+            "Offline mode needs a cache directory, but neither XDG_CACHE_HOME nor HOME is set"
+        ));
+    }
+
+    let open_meteo = || {
+        let mut provider = OpenMeteo::new(client.clone(), &config.lang)
+            .with_retry(RetryPolicy::from_config(config));
+        if let Some(url) = &config.open_meteo_url {
+            provider = provider.with_base_url(url);
+        }
+        if let Some(cache) = &cache {
+            provider = provider.with_cache(cache.clone());
+        }
+        provider
+    };
+
+    match config.provider {
+        ProviderKind::OpenMeteo => Ok(Box::new(open_meteo())),
+        ProviderKind::VisualCrossing => {
+            let Some(api_key) = &config.api_key else {
+                if config.fallback {
+                    eprintln!("No Visual Crossing API key configured, using Open-Meteo");
+                    return Ok(Box::new(open_meteo()));
+                }
+                return Err(anyhow::anyhow!(
+                    "Visual Crossing needs an API key: set api_key in the config file, WEATHER_APP_API_KEY or --api-key"
+                ));
+            };
+
+            let mut visual_crossing = VisualCrossing::new(client.clone(), api_key, &config.lang)
+                .with_retry(RetryPolicy::from_config(config));
+            if let Some(cache) = &cache {
+                visual_crossing = visual_crossing.with_cache(cache.clone());
+            }
+            if config.fallback {
+                Ok(Box::new(Fallback::new(
+                    Box::new(visual_crossing),
+                    Box::new(open_meteo()),
+                )))
+            } else {
+                Ok(Box::new(visual_crossing))
+            }
+        }
+    }
+}
@@ -0,0 +1,677 @@
+use super::{WeatherProvider, get_text};
+use crate::astro;
+use crate::cache::{CachedResponse, ResponseCache};
+use crate::error::WeatherError;
+use crate::geocoding::{Geocoder, OpenMeteoGeocoder, Place};
+use crate::retry::{CircuitBreaker, RetryPolicy};
+use crate::weather_structs::{CurrentConditions, ForecastDay, ForecastHour, ForecastResponse};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+use std::sync::Arc;
+
+const FORECAST_URL: &str = "https://api.open-meteo.com";
+const AIR_QUALITY_URL: &str = "https://air-quality-api.open-meteo.com";
+
+// places per request in `current_many`, which keeps the URL a sane length
+const BATCH_SIZE: usize = 50;
+
+// today + the next 7 days, same as Visual Crossing's `next7days`
+const FORECAST_DAYS: u32 = 8;
+
+const DAILY_FIELDS: &str = "weather_code,temperature_2m_max,temperature_2m_min,temperature_2m_mean,apparent_temperature_mean,relative_humidity_2m_mean,precipitation_sum,precipitation_probability_max,snowfall_sum,wind_speed_10m_max,wind_direction_10m_dominant,sunrise,sunset";
+const HOURLY_FIELDS: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,precipitation,precipitation_probability,wind_speed_10m,wind_direction_10m,cloud_cover,weather_code,is_day,snow_depth";
+
+/// Keyless provider backed by open-meteo.com
+pub struct OpenMeteo {
+    forecast_url: String,
+    air_quality_url: String,
+    geocoder: OpenMeteoGeocoder,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+    cache: Option<Arc<ResponseCache>>,
+}
+
+impl OpenMeteo {
+    pub fn new(client: reqwest::Client, lang: impl Into<String>) -> Self {
+        Self {
+            forecast_url: FORECAST_URL.to_string(),
+            air_quality_url: AIR_QUALITY_URL.to_string(),
+            geocoder: OpenMeteoGeocoder::new(client.clone(), lang),
+            client,
+            retry: RetryPolicy::default(),
+            breaker: CircuitBreaker::new("open-meteo"),
+            cache: None,
+        }
+    }
+
+    /// Points all three Open-Meteo APIs at one host, e.g. a local server replaying recorded JSON
+    pub fn with_base_url(self, base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into();
+        Self {
+            forecast_url: base_url.clone(),
+            air_quality_url: base_url.clone(),
+            geocoder: self.geocoder.with_base_url(base_url),
+            ..self
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self {
+            geocoder: self.geocoder.with_retry(retry),
+            retry,
+            ..self
+        }
+    }
+
+    pub fn with_cache(self, cache: Arc<ResponseCache>) -> Self {
+        Self {
+            geocoder: self.geocoder.with_cache(cache.clone()),
+            cache: Some(cache),
+            ..self
+        }
+    }
+
+    async fn get(&self, url: &str) -> Result<CachedResponse, WeatherError> {
+        let cache_key = format!("{}|{url}", self.name());
+        get_text(
+            &self.client,
+            &self.retry,
+            &self.breaker,
+            self.cache.as_deref(),
+            &cache_key,
+            url,
+        )
+        .await
+    }
+
+    async fn geocode(&self, location: &str) -> Result<Place, WeatherError> {
+        self.geocoder
+            .search(location, 1)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(WeatherError::NotFound)
+    }
+
+    async fn fetch_air_quality(&self, lat: f64, lon: f64) -> Result<OmHourlyAqi, WeatherError> {
+        let url = format!(
+            "{}/v1/air-quality?latitude={lat}&longitude={lon}&hourly=european_aqi&timezone=auto&forecast_days={FORECAST_DAYS}",
+            self.air_quality_url
+        );
+        let response = self.get(&url).await?;
+        let response: OmAirQualityResponse = serde_json::from_str(&response.body)?;
+
+        Ok(response.hourly)
+    }
+
+    async fn fetch_forecast(
+        &self,
+        lat: f64,
+        lon: f64,
+        resolved_address: String,
+    ) -> Result<ForecastResponse, WeatherError> {
+        let url = format!(
+            "{}/v1/forecast?latitude={lat}&longitude={lon}&daily={DAILY_FIELDS}&hourly={HOURLY_FIELDS}&timezone=auto&forecast_days={FORECAST_DAYS}",
+            self.forecast_url
+        );
+
+        let (forecast, air_quality) =
+            tokio::join!(self.get(&url), self.fetch_air_quality(lat, lon));
+        let response = forecast?;
+        let forecast: OmForecastResponse = serde_json::from_str(&response.body)?;
+
+        // the AQI is a nice-to-have, the forecast is still usable without it
+        let air_quality = match air_quality {
+            Ok(aqi) => Some(aqi),
+            Err(e) => {
+                eprintln!("Failed to get air quality for {lat},{lon}: {e}");
+                None
+            }
+        };
+
+        let mut forecast = to_forecast_response(forecast, air_quality, resolved_address);
+        forecast.fetched_at = response.fetched_at;
+        forecast.stale = response.stale;
+
+        Ok(forecast)
+    }
+
+    async fn fetch_current(
+        &self,
+        points: &[(f64, f64)],
+    ) -> Result<Vec<CurrentConditions>, WeatherError> {
+        let join = |coordinate: fn(&(f64, f64)) -> f64| {
+            points
+                .iter()
+                .map(|point| coordinate(point).to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let url = format!(
+            "{}/v1/forecast?latitude={}&longitude={}&current=temperature_2m,weather_code,is_day&daily=temperature_2m_max,temperature_2m_min&timezone=auto&forecast_days=1",
+            self.forecast_url,
+            join(|point| point.0),
+            join(|point| point.1)
+        );
+        let response = self.get(&url).await?;
+        let responses = match serde_json::from_str(&response.body)? {
+            OmOneOrMany::One(response) => vec![response],
+            OmOneOrMany::Many(responses) => responses,
+        };
+        if responses.len() != points.len() {
+            return Err(WeatherError::Parse(format!(
+                "asked for {} locations, got {}",
+                points.len(),
+                responses.len()
+            )));
+        }
+
+        Ok(points
+            .iter()
+            .zip(responses)
+            .map(|(&(lat, lon), response)| CurrentConditions {
+                resolved_address: format!("{lat}, {lon}"),
+                icon: wmo_icon(response.current.weather_code, response.current.is_day != 0)
+                    .to_string(),
+                temp: response.current.temperature_2m,
+                temp_min: first_or_zero(&response.daily.temperature_2m_min),
+                temp_max: first_or_zero(&response.daily.temperature_2m_max),
+                // Open-Meteo has no alerts API
+                alerts: Vec::new(),
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for OpenMeteo {
+    fn name(&self) -> &'static str {
+        "open-meteo"
+    }
+
+    async fn forecast(&self, location: &str) -> Result<ForecastResponse, WeatherError> {
+        let place = self.geocode(location).await?;
+        let address = place.display_name();
+
+        self.fetch_forecast(place.lat, place.lon, address).await
+    }
+
+    async fn forecast_by_coords(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<ForecastResponse, WeatherError> {
+        self.fetch_forecast(lat, lon, format!("{lat}, {lon}")).await
+    }
+
+    async fn current(&self, lat: f64, lon: f64) -> Result<CurrentConditions, WeatherError> {
+        self.fetch_current(&[(lat, lon)])
+            .await?
+            .pop()
+            .ok_or_else(|| WeatherError::Parse("API returned no locations".to_string()))
+    }
+
+    // the forecast API takes comma separated coordinates and answers with a list
+    async fn current_many(
+        &self,
+        points: &[(f64, f64)],
+    ) -> Vec<Result<CurrentConditions, WeatherError>> {
+        let mut results = Vec::with_capacity(points.len());
+        for batch in points.chunks(BATCH_SIZE) {
+            match self.fetch_current(batch).await {
+                Ok(current) => results.extend(current.into_iter().map(Ok)),
+                Err(e) => results.extend(batch.iter().map(|_| Err(e.clone()))),
+            }
+        }
+        results
+    }
+}
+
+/// Maps a WMO weather interpretation code onto the icon names from `src/assets/icons`
+fn wmo_icon(code: u8, is_day: bool) -> &'static str {
+    match code {
+        0 if is_day => "clear-day",
+        0 => "clear-night",
+        1 | 2 if is_day => "partly-cloudy-day",
+        1 | 2 => "partly-cloudy-night",
+        3 => "cloudy",
+        45 | 48 => "fog",
+        51 | 53 | 55 | 61 | 63 | 65 | 80 | 81 | 82 => "rain",
+        56 | 57 | 66 | 67 => "sleet",
+        71 | 73 | 75 | 77 | 85 | 86 => "snow",
+        95 => "thunder-rain",
+        96 | 99 => "hail",
+        _ => "default",
+    }
+}
+
+fn wmo_conditions(code: u8) -> &'static str {
+    match code {
+        0 => "Clear",
+        1 => "Mainly clear",
+        2 => "Partially cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 => "Drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 | 63 | 65 => "Rain",
+        66 | 67 => "Freezing rain",
+        71 | 73 | 75 | 77 => "Snow",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+fn first_or_zero(values: &[Option<f32>]) -> f32 {
+    values.first().copied().flatten().unwrap_or(0.0)
+}
+
+fn value_at(values: &[Option<f32>], i: usize) -> f32 {
+    values.get(i).copied().flatten().unwrap_or(0.0)
+}
+
+fn to_forecast_response(
+    forecast: OmForecastResponse,
+    air_quality: Option<OmHourlyAqi>,
+    resolved_address: String,
+) -> ForecastResponse {
+    let daily = &forecast.daily;
+    let hourly = &forecast.hourly;
+
+    let days = daily
+        .time
+        .iter()
+        .enumerate()
+        .map(|(i, date)| {
+            let hour_indices: Vec<usize> = hourly
+                .time
+                .iter()
+                .enumerate()
+                .filter(|(_, time)| time.starts_with(date.as_str()))
+                .map(|(j, _)| j)
+                .collect();
+
+            let hours = hour_indices
+                .iter()
+                .map(|&j| {
+                    let code = hourly.weather_code.get(j).copied().flatten().unwrap_or(0);
+                    let is_day = hourly.is_day.get(j).copied().flatten().unwrap_or(1) == 1;
+
+                    ForecastHour {
+                        datetime: hour_of(&hourly.time[j]),
+                        temp: value_at(&hourly.temperature_2m, j),
+                        feels_like: value_at(&hourly.apparent_temperature, j),
+                        humidity: value_at(&hourly.relative_humidity_2m, j),
+                        precip: value_at(&hourly.precipitation, j),
+                        precip_prob: value_at(&hourly.precipitation_probability, j),
+                        wind_speed: value_at(&hourly.wind_speed_10m, j),
+                        wind_dir: value_at(&hourly.wind_direction_10m, j),
+                        cloud_cover: value_at(&hourly.cloud_cover, j),
+                        conditions: wmo_conditions(code).to_string(),
+                        icon: wmo_icon(code, is_day).to_string(),
+                    }
+                })
+                .collect();
+
+            // snow depth comes in meters, Visual Crossing reports it in cm
+            let snow_depth = hour_indices
+                .iter()
+                .map(|&j| value_at(&hourly.snow_depth, j) * 100.0)
+                .fold(0.0, f32::max);
+
+            let aqieur = air_quality.as_ref().and_then(|aqi| {
+                aqi.time
+                    .iter()
+                    .zip(&aqi.european_aqi)
+                    .filter(|(time, _)| time.starts_with(date.as_str()))
+                    .filter_map(|(_, value)| *value)
+                    .reduce(f32::max)
+            });
+
+            let code = daily.weather_code.get(i).copied().flatten().unwrap_or(0);
+
+            ForecastDay {
+                datetime: date.clone(),
+                temp_max: value_at(&daily.temperature_2m_max, i),
+                temp_min: value_at(&daily.temperature_2m_min, i),
+                temp: value_at(&daily.temperature_2m_mean, i),
+                feels_like: value_at(&daily.apparent_temperature_mean, i),
+                humidity: value_at(&daily.relative_humidity_2m_mean, i),
+                precip: value_at(&daily.precipitation_sum, i),
+                precip_prob: value_at(&daily.precipitation_probability_max, i),
+                snow: value_at(&daily.snowfall_sum, i),
+                snow_depth,
+                wind_speed: value_at(&daily.wind_speed_10m_max, i),
+                wind_dir: value_at(&daily.wind_direction_10m_dominant, i),
+                aqieur,
+                severe_risk: None,
+                sunrise: daily
+                    .sunrise
+                    .get(i)
+                    .cloned()
+                    .flatten()
+                    .map(|time| hour_of(&time)),
+                sunset: daily
+                    .sunset
+                    .get(i)
+                    .cloned()
+                    .flatten()
+                    .map(|time| hour_of(&time)),
+                daylight: None,
+                daylight_change: None,
+                // Open-Meteo has no moon data, astro::fill_missing computes it
+                moon_phase: None,
+                conditions: wmo_conditions(code).to_string(),
+                icon: wmo_icon(code, true).to_string(),
+                hours,
+            }
+        })
+        .collect();
+
+    let mut response = ForecastResponse {
+        resolved_address,
+        latitude: forecast.latitude,
+        longitude: forecast.longitude,
+        days,
+        alerts: Vec::new(),
+        fetched_at: Utc::now(),
+        stale: false,
+    };
+    astro::fill_missing(&mut response, forecast.utc_offset_seconds as f64 / 3600.0);
+
+    response
+}
+
+// "2025-01-31T14:00" -> "14:00:00", the same shape Visual Crossing uses for hours
+fn hour_of(time: &str) -> String {
+    match time.split('T').nth(1) {
+        Some(hour) => format!("{hour}:00"),
+        None => "N/A".to_string(),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct OmForecastResponse {
+    latitude: f64,
+    longitude: f64,
+    utc_offset_seconds: i32,
+    daily: OmDaily,
+    hourly: OmHourly,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmDaily {
+    time: Vec<String>,
+    weather_code: Vec<Option<u8>>,
+    temperature_2m_max: Vec<Option<f32>>,
+    temperature_2m_min: Vec<Option<f32>>,
+    temperature_2m_mean: Vec<Option<f32>>,
+    apparent_temperature_mean: Vec<Option<f32>>,
+    relative_humidity_2m_mean: Vec<Option<f32>>,
+    precipitation_sum: Vec<Option<f32>>,
+    precipitation_probability_max: Vec<Option<f32>>,
+    snowfall_sum: Vec<Option<f32>>,
+    wind_speed_10m_max: Vec<Option<f32>>,
+    wind_direction_10m_dominant: Vec<Option<f32>>,
+    sunrise: Vec<Option<String>>,
+    sunset: Vec<Option<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmHourly {
+    time: Vec<String>,
+    temperature_2m: Vec<Option<f32>>,
+    apparent_temperature: Vec<Option<f32>>,
+    relative_humidity_2m: Vec<Option<f32>>,
+    precipitation: Vec<Option<f32>>,
+    precipitation_probability: Vec<Option<f32>>,
+    wind_speed_10m: Vec<Option<f32>>,
+    wind_direction_10m: Vec<Option<f32>>,
+    cloud_cover: Vec<Option<f32>>,
+    weather_code: Vec<Option<u8>>,
+    is_day: Vec<Option<u8>>,
+    snow_depth: Vec<Option<f32>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmAirQualityResponse {
+    hourly: OmHourlyAqi,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmHourlyAqi {
+    time: Vec<String>,
+    european_aqi: Vec<Option<f32>>,
+}
+
+// one location comes back as an object, several as a list of them
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum OmOneOrMany {
+    One(OmCurrentResponse),
+    Many(Vec<OmCurrentResponse>),
+}
+
+#[derive(Deserialize, Debug)]
+struct OmCurrentResponse {
+    current: OmCurrent,
+    daily: OmCurrentDaily,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmCurrent {
+    temperature_2m: f32,
+    weather_code: u8,
+    is_day: u8,
+}
+
+#[derive(Deserialize, Debug)]
+struct OmCurrentDaily {
+    temperature_2m_max: Vec<Option<f32>>,
+    temperature_2m_min: Vec<Option<f32>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{FixtureServer, Reply, client};
+    use serde_json::Value;
+
+    const FORECAST: &str = include_str!("../../fixtures/open_meteo/forecast.json");
+    const AIR_QUALITY: &str = include_str!("../../fixtures/open_meteo/air_quality.json");
+    const CURRENT: &str = include_str!("../../fixtures/open_meteo/current.json");
+
+    fn provider(server: &FixtureServer) -> OpenMeteo {
+        OpenMeteo::new(client(), "en")
+            .with_base_url(&server.url)
+            .with_retry(RetryPolicy {
+                max_retries: 0,
+                ..RetryPolicy::default()
+            })
+    }
+
+    fn query_param<'a>(target: &'a str, name: &str) -> &'a str {
+        target
+            .split_once('?')
+            .map_or("", |(_, query)| query)
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+            .unwrap_or("")
+    }
+
+    // the recorded single-location answer, once per requested latitude, with the
+    // latitude as the temperature so the order can be checked
+    fn current_batch(target: &str) -> Reply {
+        let latitudes: Vec<f64> = query_param(target, "latitude")
+            .split(',')
+            .map(|lat| lat.parse().unwrap())
+            .collect();
+        let template: Value = serde_json::from_str(CURRENT).unwrap();
+        let locations: Vec<Value> = latitudes
+            .iter()
+            .map(|&lat| {
+                let mut location = template.clone();
+                location["current"]["temperature_2m"] = lat.into();
+                location
+            })
+            .collect();
+        match locations.as_slice() {
+            [one] => Reply::json(one.to_string()),
+            _ => Reply::json(Value::from(locations).to_string()),
+        }
+    }
+
+    #[test]
+    fn wmo_codes_map_to_icons() {
+        let cases = [
+            (0, true, "clear-day"),
+            (0, false, "clear-night"),
+            (2, true, "partly-cloudy-day"),
+            (1, false, "partly-cloudy-night"),
+            (3, false, "cloudy"),
+            (48, true, "fog"),
+            (53, true, "rain"),
+            (82, true, "rain"),
+            (67, true, "sleet"),
+            (86, true, "snow"),
+            (95, true, "thunder-rain"),
+            (99, true, "hail"),
+            (42, true, "default"),
+        ];
+        for (code, is_day, icon) in cases {
+            assert_eq!(wmo_icon(code, is_day), icon, "WMO code {code}");
+        }
+    }
+
+    #[tokio::test]
+    async fn forecast_maps_recorded_response() {
+        let server = FixtureServer::start(|target| {
+            if target.starts_with("/v1/air-quality") {
+                Reply::json(AIR_QUALITY)
+            } else {
+                Reply::json(FORECAST)
+            }
+        })
+        .await;
+
+        let forecast = provider(&server)
+            .forecast_by_coords(47.16, 27.58)
+            .await
+            .unwrap();
+
+        assert_eq!(forecast.days.len(), 2);
+        let [first, second] = &forecast.days[..] else {
+            unreachable!()
+        };
+
+        assert_eq!(first.datetime, "2025-01-30");
+        assert_eq!(first.icon, "snow");
+        assert_eq!(first.conditions, "Snow");
+        assert_eq!(first.temp_min, -6.3);
+        assert_eq!(first.precip_prob, 90.0);
+        assert_eq!(first.sunrise.as_deref(), Some("07:34:00"));
+        assert_eq!(second.icon, "cloudy");
+
+        let icons: Vec<&str> = first.hours.iter().map(|hour| hour.icon.as_str()).collect();
+        assert_eq!(icons, ["clear-night", "hail", "fog"]);
+        let icons: Vec<&str> = second.hours.iter().map(|hour| hour.icon.as_str()).collect();
+        assert_eq!(icons, ["thunder-rain", "partly-cloudy-day", "rain"]);
+        assert_eq!(first.hours[1].datetime, "12:00:00");
+
+        // the deepest hour of the day, meters turned into cm; missing hours count as 0
+        assert!(
+            (first.snow_depth - 15.5).abs() < 1e-3,
+            "{}",
+            first.snow_depth
+        );
+        assert!(
+            (second.snow_depth - 10.0).abs() < 1e-3,
+            "{}",
+            second.snow_depth
+        );
+
+        // the worst hour of each day, gaps skipped
+        assert_eq!(first.aqieur, Some(48.0));
+        assert_eq!(second.aqieur, Some(33.0));
+
+        assert!(first.moon_phase.is_some());
+        assert!(first.daylight.is_some());
+    }
+
+    #[tokio::test]
+    async fn forecast_without_air_quality() {
+        let server = FixtureServer::start(|target| {
+            if target.starts_with("/v1/air-quality") {
+                Reply::status(500)
+            } else {
+                Reply::json(FORECAST)
+            }
+        })
+        .await;
+
+        let forecast = provider(&server)
+            .forecast_by_coords(47.16, 27.58)
+            .await
+            .unwrap();
+
+        assert!(forecast.days.iter().all(|day| day.aqieur.is_none()));
+    }
+
+    #[tokio::test]
+    async fn current_single_location() {
+        let server = FixtureServer::start(|_| Reply::json(CURRENT)).await;
+
+        let current = provider(&server).current(47.16, 27.58).await.unwrap();
+
+        assert_eq!(current.icon, "clear-night");
+        assert_eq!(current.temp, -4.3);
+        assert_eq!((current.temp_min, current.temp_max), (-6.3, 1.2));
+    }
+
+    #[tokio::test]
+    async fn current_many_batches_in_order() {
+        let server = FixtureServer::start(current_batch).await;
+        let points: Vec<(f64, f64)> = (0..120).map(|i| (i as f64 / 4.0, 27.5)).collect();
+
+        let results = provider(&server).current_many(&points).await;
+
+        let batches: Vec<usize> = server
+            .requests()
+            .iter()
+            .map(|target| query_param(target, "latitude").split(',').count())
+            .collect();
+        assert_eq!(batches, [50, 50, 20]);
+
+        assert_eq!(results.len(), points.len());
+        for (result, (lat, _)) in results.iter().zip(&points) {
+            assert_eq!(result.as_ref().unwrap().temp, *lat as f32);
+        }
+    }
+
+    #[tokio::test]
+    async fn current_many_fails_only_the_broken_batch() {
+        let server = FixtureServer::start(|target| {
+            if query_param(target, "latitude").starts_with("50") {
+                Reply::status(503)
+            } else {
+                current_batch(target)
+            }
+        })
+        .await;
+        let points: Vec<(f64, f64)> = (0..70).map(|i| (i as f64, 27.5)).collect();
+
+        let results = provider(&server).current_many(&points).await;
+
+        assert!(results[..50].iter().all(Result::is_ok));
+        assert!(
+            results[50..]
+                .iter()
+                .all(|result| matches!(result, Err(WeatherError::ProviderOutage(503))))
+        );
+    }
+}
@@ -0,0 +1,475 @@
+use super::{WeatherProvider, get_text};
+use crate::astro;
+use crate::cache::{CachedResponse, ResponseCache};
+use crate::error::WeatherError;
+use crate::retry::{CircuitBreaker, RetryPolicy};
+use crate::weather_structs::{
+    AlertSeverity, CurrentConditions, ForecastDay, ForecastHour, ForecastResponse, WeatherAlert,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+const BASE_URL: &str =
+    "https://weather.visualcrossing.com/VisualCrossingWebServices/rest/services/timeline";
+
+const FORECAST_ELEMENTS: &str = "add%3Aaqieur%2Cadd%3Atzoffset%2Cremove%3AdatetimeEpoch%2Cremove%3Adew%2Cremove%3Afeelslikemax%2Cremove%3Afeelslikemin%2Cremove%3Aprecipcover%2Cremove%3Apreciptype%2Cremove%3Apressure%2Cremove%3Asolarenergy%2Cremove%3Asolarradiation%2Cremove%3Astations%2Cremove%3Auvindex%2Cremove%3Awindgust";
+
+pub struct VisualCrossing {
+    api_key: String,
+    lang: String,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+    cache: Option<Arc<ResponseCache>>,
+}
+
+impl VisualCrossing {
+    pub fn new(
+        client: reqwest::Client,
+        api_key: impl Into<String>,
+        lang: impl Into<String>,
+    ) -> Self {
+        Self {
+            api_key: api_key.into(),
+            lang: lang.into(),
+            client,
+            retry: RetryPolicy::default(),
+            breaker: CircuitBreaker::new("visual-crossing"),
+            cache: None,
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+
+    pub fn with_cache(self, cache: Arc<ResponseCache>) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
+    }
+
+    // the key is appended last so the rest of the URL can double as the cache key
+    async fn get(&self, url_without_key: String) -> Result<CachedResponse, WeatherError> {
+        let url = format!("{url_without_key}&key={}", self.api_key);
+        let cache_key = format!("{}|{url_without_key}", self.name());
+        get_text(
+            &self.client,
+            &self.retry,
+            &self.breaker,
+            self.cache.as_deref(),
+            &cache_key,
+            &url,
+        )
+        .await
+    }
+
+    async fn fetch_forecast(&self, location: &str) -> Result<ForecastResponse, WeatherError> {
+        let response = self
+            .get(format!(
+                "{}?unitGroup=metric&elements={FORECAST_ELEMENTS}&lang={}&options=minuteinterval_10&include=days,hours,alerts&contentType=json",
+                timeline_url(location, "next7days"),
+                self.lang
+            ))
+            .await?;
+        let forecast: VcForecastResponse = serde_json::from_str(&response.body)?;
+
+        Ok(to_forecast_response(
+            forecast,
+            response.fetched_at,
+            response.stale,
+        ))
+    }
+}
+
+/// The Timeline API takes the location as a path segment, so names like
+/// "Saint-Étienne / Loire" have to be percent-encoded, slashes included
+fn timeline_url(location: &str, period: &str) -> String {
+    let mut url = reqwest::Url::parse(BASE_URL).expect("BASE_URL is a valid URL");
+    url.path_segments_mut()
+        .expect("BASE_URL has a path")
+        .push(location)
+        .push(period);
+    url.into()
+}
+
+#[async_trait]
+impl WeatherProvider for VisualCrossing {
+    fn name(&self) -> &'static str {
+        "visual-crossing"
+    }
+
+    async fn forecast(&self, location: &str) -> Result<ForecastResponse, WeatherError> {
+        self.fetch_forecast(location).await
+    }
+
+    async fn forecast_by_coords(
+        &self,
+        lat: f64,
+        lon: f64,
+    ) -> Result<ForecastResponse, WeatherError> {
+        self.fetch_forecast(&format!("{lat},{lon}")).await
+    }
+
+    // the Timeline API takes one location per request, so `current_many` keeps the
+    // default fan-out
+    async fn current(&self, lat: f64, lon: f64) -> Result<CurrentConditions, WeatherError> {
+        let response = self
+            .get(format!(
+                "{}?unitGroup=metric&elements=temp,tempmax,tempmin,icon&contentType=json&include=days,current,alerts",
+                timeline_url(&format!("{lat},{lon}"), "today")
+            ))
+            .await?;
+        let response: VcCurrentResponse = serde_json::from_str(&response.body)?;
+
+        let today = response
+            .days
+            .first()
+            .ok_or_else(|| WeatherError::Parse("API returned no forecast days".to_string()))?;
+
+        Ok(CurrentConditions {
+            resolved_address: response.resolved_address,
+            icon: today.icon.clone(),
+            temp: today.temp,
+            temp_min: today.tempmin,
+            temp_max: today.tempmax,
+            alerts: response
+                .alerts
+                .into_iter()
+                .map(WeatherAlert::from)
+                .collect(),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct VcForecastResponse {
+    #[serde(rename = "resolvedAddress")]
+    resolved_address: String,
+
+    latitude: f64,
+    longitude: f64,
+    /// Hours from UTC at the location
+    tzoffset: Option<f64>,
+
+    days: Vec<VcDay>,
+    #[serde(default)]
+    alerts: Vec<VcAlert>,
+}
+
+#[derive(Deserialize, Debug)]
+struct VcDay {
+    datetime: String,
+
+    tempmax: f32,
+    tempmin: f32,
+    temp: f32,
+    feelslike: f32,
+
+    humidity: f32,
+    precip: f32,
+    precipprob: f32,
+
+    snow: f32,
+    snowdepth: f32,
+
+    windspeed: f32,
+    winddir: f32,
+    aqieur: Option<f32>,
+    severerisk: Option<f32>,
+
+    sunrise: Option<String>,
+    sunset: Option<String>,
+    moonphase: Option<f32>,
+
+    conditions: String,
+    icon: String,
+    hours: Vec<VcHour>,
+}
+
+#[derive(Deserialize, Debug)]
+struct VcHour {
+    datetime: String,
+
+    temp: f32,
+    feelslike: f32,
+
+    humidity: f32,
+    precip: Option<f32>,
+    precipprob: Option<f32>,
+
+    windspeed: Option<f32>,
+    winddir: Option<f32>,
+    cloudcover: Option<f32>,
+
+    conditions: String,
+    icon: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct VcCurrentDay {
+    tempmax: f32,
+    tempmin: f32,
+    temp: f32,
+    icon: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct VcCurrentResponse {
+    #[serde(rename = "resolvedAddress")]
+    resolved_address: String,
+
+    days: Vec<VcCurrentDay>,
+    #[serde(default)]
+    alerts: Vec<VcAlert>,
+}
+
+#[derive(Deserialize, Debug)]
+struct VcAlert {
+    event: String,
+    #[serde(default)]
+    description: String,
+    /// CAP severity ("Extreme" to "Minor"), which only some alert sources pass on
+    severity: Option<String>,
+
+    #[serde(rename = "onsetEpoch")]
+    onset_epoch: Option<i64>,
+    #[serde(rename = "endsEpoch")]
+    ends_epoch: Option<i64>,
+}
+
+fn to_forecast_response(
+    resp: VcForecastResponse,
+    fetched_at: DateTime<Utc>,
+    stale: bool,
+) -> ForecastResponse {
+    let tz_offset = resp.tzoffset.unwrap_or(resp.longitude / 15.0);
+    let mut forecast = ForecastResponse {
+        resolved_address: resp.resolved_address,
+        latitude: resp.latitude,
+        longitude: resp.longitude,
+        days: resp.days.into_iter().map(ForecastDay::from).collect(),
+        alerts: resp.alerts.into_iter().map(WeatherAlert::from).collect(),
+        fetched_at,
+        stale,
+    };
+    astro::fill_missing(&mut forecast, tz_offset);
+
+    forecast
+}
+
+impl From<VcDay> for ForecastDay {
+    fn from(day: VcDay) -> Self {
+        ForecastDay {
+            datetime: day.datetime,
+            temp_max: day.tempmax,
+            temp_min: day.tempmin,
+            temp: day.temp,
+            feels_like: day.feelslike,
+            humidity: day.humidity,
+            precip: day.precip,
+            precip_prob: day.precipprob,
+            snow: day.snow,
+            snow_depth: day.snowdepth,
+            wind_speed: day.windspeed,
+            wind_dir: day.winddir,
+            aqieur: day.aqieur,
+            severe_risk: day.severerisk,
+            sunrise: day.sunrise,
+            sunset: day.sunset,
+            daylight: None,
+            daylight_change: None,
+            moon_phase: day.moonphase,
+            conditions: day.conditions,
+            icon: day.icon,
+            hours: day
+                .hours
+                .into_iter()
+                .map(|hour| ForecastHour {
+                    datetime: hour.datetime,
+                    temp: hour.temp,
+                    feels_like: hour.feelslike,
+                    humidity: hour.humidity,
+                    // hourly values are null rather than 0 when nothing is expected
+                    precip: hour.precip.unwrap_or(0.0),
+                    precip_prob: hour.precipprob.unwrap_or(0.0),
+                    wind_speed: hour.windspeed.unwrap_or(0.0),
+                    wind_dir: hour.winddir.unwrap_or(0.0),
+                    cloud_cover: hour.cloudcover.unwrap_or(0.0),
+                    conditions: hour.conditions,
+                    icon: hour.icon,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<VcAlert> for WeatherAlert {
+    fn from(alert: VcAlert) -> Self {
+        WeatherAlert {
+            severity: alert
+                .severity
+                .as_deref()
+                .and_then(cap_severity)
+                .unwrap_or_else(|| severity_of(&alert.event)),
+            onset: alert
+                .onset_epoch
+                .and_then(|epoch| DateTime::from_timestamp(epoch, 0)),
+            ends: alert
+                .ends_epoch
+                .and_then(|epoch| DateTime::from_timestamp(epoch, 0)),
+            area: area_of(&alert.description),
+            event: alert.event,
+            description: alert.description.trim().to_string(),
+        }
+    }
+}
+
+// The CAP levels, as sent by the sources that include them. "Unknown" counts as not
+// sent, so the event name still gets a say.
+fn cap_severity(severity: &str) -> Option<AlertSeverity> {
+    match severity.trim().to_ascii_lowercase().as_str() {
+        "extreme" => Some(AlertSeverity::Extreme),
+        "severe" => Some(AlertSeverity::Severe),
+        "moderate" => Some(AlertSeverity::Moderate),
+        "minor" => Some(AlertSeverity::Minor),
+        _ => None,
+    }
+}
+
+// Without a severity in the payload, event names follow the usual "Warning" > "Watch" >
+// "Advisory" convention, or spell the level out ("Moderate wind")
+fn severity_of(event: &str) -> AlertSeverity {
+    let event = event.to_lowercase();
+    let has = |word: &str| event.split_whitespace().any(|w| w == word);
+
+    if has("extreme") || has("emergency") {
+        AlertSeverity::Extreme
+    } else if has("warning") || has("severe") {
+        AlertSeverity::Severe
+    } else if has("watch") || has("moderate") {
+        AlertSeverity::Moderate
+    } else if has("advisory") || has("statement") || has("minor") {
+        AlertSeverity::Minor
+    } else {
+        AlertSeverity::Unknown
+    }
+}
+
+// NWS descriptions have a "* WHERE...Portions of ..." paragraph
+fn area_of(description: &str) -> Option<String> {
+    let (_, rest) = description.split_once("* WHERE...")?;
+    let area = rest.split("\n\n").next()?.split("\n*").next()?;
+    let area = area.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!area.is_empty()).then_some(area)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORECAST: &str = include_str!("../../fixtures/visual_crossing/forecast.json");
+
+    fn alert(json: &str) -> WeatherAlert {
+        WeatherAlert::from(serde_json::from_str::<VcAlert>(json).unwrap())
+    }
+
+    #[test]
+    fn event_names_give_the_severity() {
+        let cases = [
+            ("Extreme Wind Warning", AlertSeverity::Extreme),
+            ("Civil Emergency Message", AlertSeverity::Extreme),
+            ("Severe Thunderstorm Watch", AlertSeverity::Severe),
+            ("Winter Storm Warning", AlertSeverity::Severe),
+            ("Flood Watch", AlertSeverity::Moderate),
+            ("Moderate wind", AlertSeverity::Moderate),
+            ("Wind Advisory", AlertSeverity::Minor),
+            ("Special Weather Statement", AlertSeverity::Minor),
+            ("Cod galben de vânt", AlertSeverity::Unknown),
+            // a word, not part of one
+            ("Swatches of fog", AlertSeverity::Unknown),
+        ];
+        for (event, severity) in cases {
+            assert_eq!(severity_of(event), severity, "{event}");
+        }
+    }
+
+    #[test]
+    fn payload_severity_wins_over_the_event_name() {
+        let cases = [
+            (r#""severity": "Extreme""#, AlertSeverity::Extreme),
+            (r#""severity": "severe""#, AlertSeverity::Severe),
+            (r#""severity": " Moderate ""#, AlertSeverity::Moderate),
+            (r#""severity": "Minor""#, AlertSeverity::Minor),
+            // nothing usable, so the "Advisory" in the name decides
+            (r#""severity": "Unknown""#, AlertSeverity::Minor),
+            (r#""severity": null"#, AlertSeverity::Minor),
+            (r#""headline": "no severity""#, AlertSeverity::Minor),
+        ];
+        for (field, severity) in cases {
+            let alert = alert(&format!(r#"{{"event": "Wind Advisory", {field}}}"#));
+            assert_eq!(alert.severity, severity, "{field}");
+        }
+    }
+
+    #[test]
+    fn alerts_keep_times_and_area() {
+        let alert = alert(
+            r#"{
+                "event": "Winter Storm Warning",
+                "description": "* WHAT...Heavy snow.\n\n* WHERE...Portions of\nnorthern Maine.\n\n* WHEN...Tonight.",
+                "onsetEpoch": 1738256400,
+                "endsEpoch": 1738299600
+            }"#,
+        );
+
+        assert_eq!(alert.severity, AlertSeverity::Severe);
+        assert_eq!(alert.area.as_deref(), Some("Portions of northern Maine."));
+        assert_eq!(alert.onset, DateTime::from_timestamp(1738256400, 0));
+        assert_eq!(alert.ends, DateTime::from_timestamp(1738299600, 0));
+    }
+
+    #[test]
+    fn locations_are_encoded_into_one_path_segment() {
+        let cases = [
+            ("Iasi", "Iasi"),
+            ("47.16,27.59", "47.16,27.59"),
+            ("Saint-Étienne / Loire", "Saint-%C3%89tienne%20%2F%20Loire"),
+            ("Rock & Roll?#1", "Rock%20&%20Roll%3F%231"),
+        ];
+        for (location, encoded) in cases {
+            assert_eq!(
+                timeline_url(location, "next7days"),
+                format!("{BASE_URL}/{encoded}/next7days"),
+                "{location}"
+            );
+        }
+    }
+
+    #[test]
+    fn forecast_maps_recorded_response() {
+        let response: VcForecastResponse = serde_json::from_str(FORECAST).unwrap();
+        let forecast = to_forecast_response(response, Utc::now(), false);
+
+        assert_eq!(forecast.resolved_address, "Iași, România");
+        let [first, second] = &forecast.days[..] else {
+            panic!("{} days", forecast.days.len())
+        };
+        assert_eq!(first.severe_risk, Some(30.0));
+        assert_eq!(first.aqieur, Some(2.0));
+        assert_eq!(first.moon_phase, Some(0.85));
+        assert_eq!(second.severe_risk, None);
+        assert_eq!(first.hours[1].precip_prob, 0.0);
+        // filled in locally, Visual Crossing doesn't send it
+        assert!(first.daylight.is_some());
+
+        let [alert] = &forecast.alerts[..] else {
+            panic!("{} alerts", forecast.alerts.len())
+        };
+        assert_eq!(alert.severity, AlertSeverity::Moderate);
+        assert_eq!(alert.onset, DateTime::from_timestamp(1750503600, 0));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::coords::{CoordsError, format_coords, parse_coords};
+use crate::error::WeatherError;
+use crate::geocoding::{CityIndex, Place};
+use crate::providers::WeatherProvider;
+use crate::weather_structs::ForecastResponse;
+
+/// What a forecast is fetched for, kept so it can be fetched again
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Left to the provider to find
+    Text(String),
+    /// Fetched by coordinates and shown under its own name
+    Place(Place),
+}
+
+impl Query {
+    /// Coordinates and exact city names from `cities` become a `Place`, so they're
+    /// fetched by coordinates; anything else stays text. Coordinates are named after
+    /// the city they're in, or shown as they are. Errors are coordinates that
+    /// can't be used, meant for the user.
+    pub fn from_text(text: String, cities: Option<&CityIndex>) -> Result<Self, String> {
+        match parse_coords(&text) {
+            Ok((lat, lon)) => {
+                let name = cities
+                    .and_then(|cities| cities.city_at(lat, lon))
+                    .map_or_else(|| format_coords(lat, lon), |city| city.display_name());
+                return Ok(Query::Place(Place {
+                    name,
+                    region: None,
+                    country: None,
+                    lat,
+                    lon,
+                }));
+            }
+            Err(CoordsError::Invalid(message)) => return Err(message),
+            Err(CoordsError::NotCoordinates) => {}
+        }
+
+        Ok(match cities.and_then(|cities| cities.find_exact(&text)) {
+            Some(place) => Query::Place(place),
+            None => Query::Text(text),
+        })
+    }
+
+    pub async fn fetch(
+        &self,
+        provider: &dyn WeatherProvider,
+        cities: Option<&CityIndex>,
+    ) -> Result<ForecastResponse, WeatherError> {
+        match self {
+            Query::Text(location) => {
+                let mut forecast = provider.forecast(location).await?;
+                // providers name places inconsistently, the city list doesn't
+                if let Some(city) =
+                    cities.and_then(|cities| cities.city_at(forecast.latitude, forecast.longitude))
+                {
+                    forecast.resolved_address = city.display_name();
+                }
+                Ok(forecast)
+            }
+            Query::Place(place) => {
+                let mut forecast = provider.forecast_by_coords(place.lat, place.lon).await?;
+                forecast.resolved_address = place.display_name();
+                Ok(forecast)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Query::Text(location) => write!(f, "{location}"),
+            Query::Place(place) => {
+                write!(f, "{} ({}, {})", place.display_name(), place.lat, place.lon)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geocoding::geonames_tests::index;
+    use crate::weather_structs::CurrentConditions;
+    use async_trait::async_trait;
+    use chrono::Utc;
+
+    /// Answers every text search with a forecast for the same point
+    struct FixedPoint {
+        lat: f64,
+        lon: f64,
+    }
+
+    #[async_trait]
+    impl WeatherProvider for FixedPoint {
+        fn name(&self) -> &'static str {
+            "fixed-point"
+        }
+
+        async fn forecast(&self, location: &str) -> Result<ForecastResponse, WeatherError> {
+            Ok(ForecastResponse {
+                resolved_address: format!("{location}, provider"),
+                latitude: self.lat,
+                longitude: self.lon,
+                days: Vec::new(),
+                alerts: Vec::new(),
+                fetched_at: Utc::now(),
+                stale: false,
+            })
+        }
+
+        async fn forecast_by_coords(
+            &self,
+            lat: f64,
+            lon: f64,
+        ) -> Result<ForecastResponse, WeatherError> {
+            let mut forecast = self.forecast("coords").await?;
+            (forecast.latitude, forecast.longitude) = (lat, lon);
+            Ok(forecast)
+        }
+
+        async fn current(&self, _: f64, _: f64) -> Result<CurrentConditions, WeatherError> {
+            Err(WeatherError::NotFound)
+        }
+    }
+
+    #[test]
+    fn only_exact_city_names_resolve_locally() {
+        let index = index();
+
+        match Query::from_text("Brasov".to_string(), Some(&index)) {
+            Ok(Query::Place(place)) => assert_eq!(place.name, "Brașov"),
+            other => panic!("{other:?}"),
+        }
+        assert_eq!(
+            Query::from_text("Bran".to_string(), Some(&index)),
+            Ok(Query::Text("Bran".to_string()))
+        );
+        assert_eq!(
+            Query::from_text("Brasov".to_string(), None),
+            Ok(Query::Text("Brasov".to_string()))
+        );
+    }
+
+    #[test]
+    fn coordinates_become_places() {
+        let index = index();
+
+        match Query::from_text("47.17, 27.615".to_string(), Some(&index)) {
+            Ok(Query::Place(place)) => {
+                assert_eq!(place.display_name(), "Iași, RO");
+                assert_eq!((place.lat, place.lon), (47.17, 27.615));
+            }
+            other => panic!("{other:?}"),
+        }
+        // Bran is near Brașov, not in it
+        match Query::from_text("45.515, 25.367".to_string(), Some(&index)) {
+            Ok(Query::Place(place)) => assert_eq!(place.name, format_coords(45.515, 25.367)),
+            other => panic!("{other:?}"),
+        }
+        match Query::from_text("47.17, 27.615".to_string(), None) {
+            Ok(Query::Place(place)) => assert_eq!(place.name, format_coords(47.17, 27.615)),
+            other => panic!("{other:?}"),
+        }
+        assert!(Query::from_text("47.17, 227.6".to_string(), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn text_results_are_renamed_only_inside_a_city() {
+        let index = index();
+        let query = Query::Text("Bran".to_string());
+
+        let village = FixedPoint {
+            lat: 45.515,
+            lon: 25.367,
+        };
+        let forecast = query.fetch(&village, Some(&index)).await.unwrap();
+        assert_eq!(forecast.resolved_address, "Bran, provider");
+
+        let city = FixedPoint {
+            lat: 45.65,
+            lon: 25.61,
+        };
+        let forecast = query.fetch(&city, Some(&index)).await.unwrap();
+        assert_eq!(forecast.resolved_address, "Brașov, RO");
+    }
+}
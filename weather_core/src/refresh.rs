@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+// When the background refresh should run next: every `interval` while fetches work,
+// and from a minute up, doubling, while they fail or only stale data comes back.
+
+const RETRY_START: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+pub struct RefreshSchedule {
+    /// Zero means no periodic refresh, only retries after failures
+    interval: Duration,
+    failures: u32,
+    last_attempt: Instant,
+}
+
+impl RefreshSchedule {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            failures: 0,
+            last_attempt: Instant::now(),
+        }
+    }
+
+    /// How long to wait after the last attempt. `showing_stale` counts as a failure, so an
+    /// offline forecast from a search gets retried soon even if the last refresh worked.
+    pub fn delay(&self, showing_stale: bool) -> Option<Duration> {
+        let failures = if showing_stale {
+            self.failures.max(1)
+        } else {
+            self.failures
+        };
+
+        if failures == 0 {
+            return (!self.interval.is_zero()).then_some(self.interval);
+        }
+        let backoff = RETRY_START.saturating_mul(2u32.saturating_pow(failures - 1));
+        Some(backoff.min(MAX_BACKOFF.max(self.interval)))
+    }
+
+    pub fn is_due(&self, now: Instant, showing_stale: bool) -> bool {
+        self.delay(showing_stale)
+            .is_some_and(|delay| now.duration_since(self.last_attempt) >= delay)
+    }
+
+    pub fn record(&mut self, now: Instant, succeeded: bool) {
+        self.last_attempt = now;
+        self.failures = if succeeded {
+            0
+        } else {
+            self.failures.saturating_add(1)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn failed(schedule: &mut RefreshSchedule, times: u32) -> Vec<Duration> {
+        (0..times)
+            .map(|_| {
+                schedule.record(Instant::now(), false);
+                schedule.delay(false).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn failures_back_off_up_to_an_hour() {
+        let mut schedule = RefreshSchedule::new(15 * MINUTE);
+        assert_eq!(schedule.delay(false), Some(15 * MINUTE));
+
+        let minutes = [1, 2, 4, 8, 16, 32, 60, 60].map(|m| m * MINUTE);
+        assert_eq!(failed(&mut schedule, 8), minutes);
+
+        // no overflow however long it stays down
+        failed(&mut schedule, 100);
+        assert_eq!(schedule.delay(false), Some(60 * MINUTE));
+    }
+
+    #[test]
+    fn success_goes_back_to_the_interval() {
+        let mut schedule = RefreshSchedule::new(15 * MINUTE);
+        failed(&mut schedule, 5);
+        schedule.record(Instant::now(), true);
+        assert_eq!(schedule.delay(false), Some(15 * MINUTE));
+
+        // and the next failure starts again from a minute
+        assert_eq!(failed(&mut schedule, 1), [MINUTE]);
+    }
+
+    #[test]
+    fn long_intervals_cap_the_backoff() {
+        let mut schedule = RefreshSchedule::new(120 * MINUTE);
+        // never waiting longer than the refresh interval would
+        let minutes = [1, 2, 4, 8, 16, 32, 64, 120, 120].map(|m| m * MINUTE);
+        assert_eq!(failed(&mut schedule, 9), minutes);
+    }
+
+    #[test]
+    fn stale_data_is_retried_like_a_failure() {
+        let mut schedule = RefreshSchedule::new(15 * MINUTE);
+        assert_eq!(schedule.delay(true), Some(MINUTE));
+        failed(&mut schedule, 3);
+        assert_eq!(schedule.delay(true), Some(4 * MINUTE));
+    }
+
+    #[test]
+    fn zero_interval_only_retries_failures() {
+        let mut schedule = RefreshSchedule::new(Duration::ZERO);
+        let now = Instant::now();
+        assert_eq!(schedule.delay(false), None);
+        assert!(!schedule.is_due(now + 24 * 60 * MINUTE, false));
+
+        schedule.record(now, false);
+        assert!(!schedule.is_due(now + MINUTE / 2, false));
+        assert!(schedule.is_due(now + MINUTE, false));
+    }
+}
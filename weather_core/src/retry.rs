@@ -0,0 +1,389 @@
+use crate::config::Config;
+use crate::error::WeatherError;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How failed requests are tried again: exponential backoff with jitter, only for
+/// errors that can go away on their own, and never past `deadline`
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every one after it
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Total time for the first attempt and all retries, waits included
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(8),
+            deadline: Duration::from_secs(20),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        RetryPolicy {
+            max_retries: config.max_retries,
+            deadline: Duration::from_secs(config.retry_deadline_secs),
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Wait before retry number `retry` (1 for the first): somewhere between half and
+    /// all of the backoff, so clients that failed together don't retry together. A
+    /// `Retry-After` from the provider is waited out in full.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_delay);
+        let jittered = backoff / 2 + backoff.mul_f64(fastrand::f64() / 2.0);
+        jittered.max(retry_after.unwrap_or_default())
+    }
+
+    /// Runs `attempt` until it succeeds, fails with an error retrying won't fix, runs
+    /// out of retries or would need to wait past the deadline. An attempt still running
+    /// at the deadline is dropped and fails with `Timeout`. `breaker`, when given, sees
+    /// every outcome and stops the attempts while it's open.
+    pub async fn run<T, F, Fut>(
+        &self,
+        breaker: Option<&CircuitBreaker>,
+        what: &str,
+        mut attempt: F,
+    ) -> Result<T, WeatherError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, WeatherError>>,
+    {
+        let started = tokio::time::Instant::now();
+        let mut retry = 0;
+
+        loop {
+            let permit = breaker.map(CircuitBreaker::check).transpose()?;
+            let remaining = self.deadline.saturating_sub(started.elapsed());
+            let result = tokio::time::timeout(remaining, attempt())
+                .await
+                .unwrap_or(Err(WeatherError::Timeout));
+            if let Some(permit) = permit {
+                permit.record(&result);
+            }
+
+            let e = match result {
+                Ok(value) => return Ok(value),
+                Err(e) if !e.is_transient() || retry >= self.max_retries => return Err(e),
+                // tried again once the breaker lets requests through, not now
+                Err(e @ WeatherError::CircuitOpen { .. }) => return Err(e),
+                Err(e) => e,
+            };
+
+            retry += 1;
+            let retry_after = match &e {
+                WeatherError::RateLimited { retry_after } => *retry_after,
+                _ => None,
+            };
+            let delay = self.delay(retry, retry_after);
+            if started.elapsed() + delay > self.deadline {
+                return Err(e);
+            }
+
+            eprintln!("Retry {retry} for {what} in {delay:.1?}: {e}");
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Stops sending requests to a provider that keeps answering 429 or 5xx, so a failing
+/// service isn't hit again for every favorite. After `threshold` such failures in a
+/// row it opens for `cooldown` (or the provider's `Retry-After`, if longer); then one
+/// request is let through, and its outcome closes or reopens it. A trial that's
+/// cancelled before it answers hands the trial over to the next request.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BreakerState {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// The cooldown is over and one request is on its way
+    HalfOpen,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str) -> Self {
+        CircuitBreaker {
+            name,
+            threshold: 5,
+            cooldown: Duration::from_secs(60),
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Err while open, or while the trial request after the cooldown hasn't answered
+    /// yet. The permit takes the outcome of the request it lets through.
+    pub fn check(&self) -> Result<Permit<'_>, WeatherError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match *state {
+            BreakerState::Closed { .. } => Ok(Permit {
+                breaker: self,
+                trial: false,
+            }),
+            BreakerState::Open { until } => {
+                let now = Instant::now();
+                if now >= until {
+                    *state = BreakerState::HalfOpen;
+                    Ok(Permit {
+                        breaker: self,
+                        trial: true,
+                    })
+                } else {
+                    Err(WeatherError::CircuitOpen {
+                        retry_in: until - now,
+                    })
+                }
+            }
+            BreakerState::HalfOpen => Err(WeatherError::CircuitOpen {
+                retry_in: Duration::ZERO,
+            }),
+        }
+    }
+
+    fn record<T>(&self, result: &Result<T, WeatherError>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let retry_after = match result {
+            Ok(_) => {
+                *state = BreakerState::Closed { failures: 0 };
+                return;
+            }
+            Err(WeatherError::RateLimited { retry_after }) => *retry_after,
+            Err(WeatherError::ProviderOutage(_)) => None,
+            // refused by the breaker itself, no news about the provider
+            Err(WeatherError::CircuitOpen { .. }) => return,
+            // a bad request or no connection says nothing about the provider's health,
+            // but a trial request has to settle the state either way
+            Err(_) => {
+                if let BreakerState::HalfOpen = *state {
+                    *state = BreakerState::Closed { failures: 0 };
+                }
+                return;
+            }
+        };
+
+        let failures = match *state {
+            BreakerState::Closed { failures } => failures + 1,
+            BreakerState::HalfOpen => self.threshold,
+            BreakerState::Open { .. } => return,
+        };
+        if failures < self.threshold {
+            *state = BreakerState::Closed { failures };
+            return;
+        }
+
+        let cooldown = self.cooldown.max(retry_after.unwrap_or_default());
+        eprintln!(
+            "{} keeps failing, pausing requests to it for {cooldown:.0?}",
+            self.name
+        );
+        *state = BreakerState::Open {
+            until: Instant::now() + cooldown,
+        };
+    }
+}
+
+/// A request let through by a `CircuitBreaker`, to be settled with `record`
+#[derive(Debug)]
+#[must_use]
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    /// The one request allowed after the cooldown
+    trial: bool,
+}
+
+impl Permit<'_> {
+    pub fn record<T>(mut self, result: &Result<T, WeatherError>) {
+        self.breaker.record(result);
+        self.trial = false;
+    }
+}
+
+impl Drop for Permit<'_> {
+    // a trial dropped without an answer (say, a search replaced by a newer one) mustn't
+    // leave the breaker waiting for it forever
+    fn drop(&mut self) {
+        if !self.trial {
+            return;
+        }
+        let mut state = self.breaker.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let BreakerState::HalfOpen = *state {
+            *state = BreakerState::Open {
+                until: Instant::now(),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn breaker(cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            name: "test",
+            threshold: 2,
+            cooldown,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    fn outage() -> Result<(), WeatherError> {
+        Err(WeatherError::ProviderOutage(503))
+    }
+
+    fn is_open(result: Result<Permit<'_>, WeatherError>) -> bool {
+        matches!(result, Err(WeatherError::CircuitOpen { .. }))
+    }
+
+    fn no_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn breaker_opens_after_failures_in_a_row() {
+        let breaker = breaker(Duration::from_secs(60));
+
+        breaker.check().unwrap().record(&outage());
+        breaker.check().unwrap().record(&Ok(()));
+        breaker.check().unwrap().record(&outage());
+        // not found says nothing about the provider
+        breaker
+            .check()
+            .unwrap()
+            .record::<()>(&Err(WeatherError::NotFound));
+        assert!(breaker.check().is_ok());
+
+        breaker.check().unwrap().record(&outage());
+        assert!(is_open(breaker.check()));
+    }
+
+    #[test]
+    fn trial_after_cooldown_closes_or_reopens() {
+        let breaker = breaker(Duration::ZERO);
+        breaker.check().unwrap().record(&outage());
+        breaker.check().unwrap().record(&outage());
+
+        let trial = breaker.check().unwrap();
+        assert!(is_open(breaker.check()));
+        trial.record(&outage());
+
+        // reopened, and with no cooldown the next request is the next trial
+        let trial = breaker.check().unwrap();
+        assert!(is_open(breaker.check()));
+        trial.record(&Ok(()));
+
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn dropped_trial_lets_the_next_request_try() {
+        let breaker = breaker(Duration::ZERO);
+        breaker.check().unwrap().record(&outage());
+        breaker.check().unwrap().record(&outage());
+
+        drop(breaker.check().unwrap());
+
+        let trial = breaker.check().unwrap();
+        assert!(is_open(breaker.check()));
+        trial.record(&Ok(()));
+        assert!(breaker.check().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_trial_does_not_block_the_breaker() {
+        let breaker = breaker(Duration::ZERO);
+        breaker.check().unwrap().record(&outage());
+        breaker.check().unwrap().record(&outage());
+
+        // like a search task aborted while its request hangs
+        let policy = no_retries();
+        let hung = policy.run(Some(&breaker), "test", || {
+            future::pending::<Result<(), WeatherError>>()
+        });
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), hung)
+                .await
+                .is_err()
+        );
+
+        let result = policy
+            .run(Some(&breaker), "test", || async { Ok(()) })
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_attempt_is_cut_off_at_the_deadline() {
+        let policy = RetryPolicy {
+            deadline: Duration::from_secs(5),
+            ..RetryPolicy::default()
+        };
+        let started = tokio::time::Instant::now();
+
+        let result = policy
+            .run(None, "test", || async {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                Ok(())
+            })
+            .await;
+
+        assert!(matches!(result, Err(WeatherError::Timeout)));
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_transient_errors_are_retried() {
+        let policy = RetryPolicy::default();
+
+        let attempts = AtomicU32::new(0);
+        let result = policy
+            .run(None, "test", || async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => outage(),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        let attempts = AtomicU32::new(0);
+        let result = policy
+            .run(None, "test", || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(WeatherError::NotFound)
+            })
+            .await;
+        assert!(matches!(result, Err(WeatherError::NotFound)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
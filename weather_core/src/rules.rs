@@ -0,0 +1,825 @@
+use crate::config::Config;
+use crate::paths;
+use crate::units::{Units, round1};
+use crate::weather_structs::{FavoriteLocation, ForecastDay, ForecastHour, ForecastResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+// User-defined thresholds ("tomorrow's low below 0°C", "rain chance above 70% between
+// 07:00 and 09:00") checked against the favorites' forecasts.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Temp,
+    TempMin,
+    TempMax,
+    FeelsLike,
+    Humidity,
+    Precip,
+    PrecipProb,
+    Snow,
+    WindSpeed,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 9] = [
+        Metric::Temp,
+        Metric::TempMin,
+        Metric::TempMax,
+        Metric::FeelsLike,
+        Metric::Humidity,
+        Metric::Precip,
+        Metric::PrecipProb,
+        Metric::Snow,
+        Metric::WindSpeed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Metric::Temp => "Temperature",
+            Metric::TempMin => "Lowest temperature",
+            Metric::TempMax => "Highest temperature",
+            Metric::FeelsLike => "Feels like",
+            Metric::Humidity => "Humidity",
+            Metric::Precip => "Precipitation",
+            Metric::PrecipProb => "Precipitation chance",
+            Metric::Snow => "Snowfall",
+            Metric::WindSpeed => "Wind speed",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Metric> {
+        Metric::ALL.into_iter().find(|m| m.label() == label)
+    }
+
+    fn day_value(self, day: &ForecastDay) -> f32 {
+        match self {
+            Metric::Temp => day.temp,
+            Metric::TempMin => day.temp_min,
+            Metric::TempMax => day.temp_max,
+            Metric::FeelsLike => day.feels_like,
+            Metric::Humidity => day.humidity,
+            Metric::Precip => day.precip,
+            Metric::PrecipProb => day.precip_prob,
+            Metric::Snow => day.snow,
+            Metric::WindSpeed => day.wind_speed,
+        }
+    }
+
+    // an hour only has one temperature, so min/max/average all compare against it
+    fn hour_value(self, hour: &ForecastHour) -> Option<f32> {
+        match self {
+            Metric::Temp | Metric::TempMin | Metric::TempMax => Some(hour.temp),
+            Metric::FeelsLike => Some(hour.feels_like),
+            Metric::Humidity => Some(hour.humidity),
+            Metric::Precip => Some(hour.precip),
+            Metric::PrecipProb => Some(hour.precip_prob),
+            Metric::WindSpeed => Some(hour.wind_speed),
+            Metric::Snow => None,
+        }
+    }
+
+    /// Number typed in the user's units -> metric value
+    pub fn from_display(self, units: &Units, value: f32) -> f32 {
+        match self {
+            Metric::Temp | Metric::TempMin | Metric::TempMax | Metric::FeelsLike => {
+                units.temp_to_celsius(value)
+            }
+            Metric::Precip => units.precip_to_mm(value),
+            Metric::Snow => units.snow_to_cm(value),
+            Metric::WindSpeed => units.speed_to_kmh(value),
+            Metric::Humidity | Metric::PrecipProb => value,
+        }
+    }
+
+    pub fn format(self, units: &Units, value: f32) -> String {
+        match self {
+            Metric::Temp | Metric::TempMin | Metric::TempMax | Metric::FeelsLike => {
+                units.temp(value)
+            }
+            Metric::Precip => units.precip(value),
+            Metric::Snow => units.snow(value),
+            Metric::WindSpeed => units.speed(value),
+            Metric::Humidity | Metric::PrecipProb => format!("{}%", round1(value)),
+        }
+    }
+
+    pub fn unit_symbol(self, units: &Units) -> &'static str {
+        match self {
+            Metric::Temp | Metric::TempMin | Metric::TempMax | Metric::FeelsLike => {
+                units.temp_symbol()
+            }
+            Metric::Precip => units.precip_symbol(),
+            Metric::Snow => units.snow_symbol(),
+            Metric::WindSpeed => units.speed_symbol(),
+            Metric::Humidity | Metric::PrecipProb => "%",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Below,
+    Above,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 2] = [Comparison::Below, Comparison::Above];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Comparison::Below => "below",
+            Comparison::Above => "above",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Comparison> {
+        Comparison::ALL.into_iter().find(|c| c.label() == label)
+    }
+
+    fn holds(self, value: f32, threshold: f32) -> bool {
+        match self {
+            Comparison::Below => value < threshold,
+            Comparison::Above => value > threshold,
+        }
+    }
+
+    // the value furthest past the threshold is the one worth reporting
+    fn worst(self, a: f32, b: f32) -> bool {
+        match self {
+            Comparison::Below => a < b,
+            Comparison::Above => a > b,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DaySelector {
+    Today,
+    Tomorrow,
+    AnyDay,
+}
+
+impl DaySelector {
+    pub const ALL: [DaySelector; 3] = [
+        DaySelector::Today,
+        DaySelector::Tomorrow,
+        DaySelector::AnyDay,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DaySelector::Today => "today",
+            DaySelector::Tomorrow => "tomorrow",
+            DaySelector::AnyDay => "any day",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<DaySelector> {
+        DaySelector::ALL.into_iter().find(|d| d.label() == label)
+    }
+
+    fn days(self, forecast: &ForecastResponse) -> &[ForecastDay] {
+        let days = forecast.days.as_slice();
+        match self {
+            DaySelector::Today => &days[..days.len().min(1)],
+            DaySelector::Tomorrow => days.get(1..2).unwrap_or_default(),
+            DaySelector::AnyDay => days,
+        }
+    }
+}
+
+/// Hours `from` up to (not including) `to`, so 7-9 covers 07:00 and 08:00
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HourWindow {
+    pub from: u8,
+    pub to: u8,
+}
+
+impl HourWindow {
+    fn contains(self, datetime: &str) -> bool {
+        datetime
+            .get(..2)
+            .and_then(|hour| hour.parse::<u8>().ok())
+            .is_some_and(|hour| self.from <= hour && hour < self.to)
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    /// Always metric, like the forecast data: °C, km/h, mm, cm, %
+    pub threshold: f32,
+    pub day: DaySelector,
+    /// Compare hour by hour inside this window instead of using the daily value
+    #[serde(default)]
+    pub hours: Option<HourWindow>,
+    /// Only check the favorite with this address (not its display name, which can be
+    /// changed), every favorite when unset
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+impl Rule {
+    /// "Lowest temperature below 0°C tomorrow in Home", in the user's units and under
+    /// the name the favorite has in the panel
+    pub fn describe(&self, units: &Units, favorites: &[FavoriteLocation]) -> String {
+        let mut text = format!(
+            "{} {} {} {}",
+            self.metric.label(),
+            self.comparison.label(),
+            self.metric.format(units, self.threshold),
+            self.day.label()
+        );
+        if let Some(window) = self.hours {
+            text += &format!(" between {:02}:00 and {:02}:00", window.from, window.to);
+        }
+        if let Some(location) = &self.location {
+            text += &format!(" in {}", location_name(favorites, location));
+        }
+        text
+    }
+
+    fn applies_to(&self, location: &str) -> bool {
+        self.location
+            .as_deref()
+            .is_none_or(|only| only.eq_ignore_ascii_case(location))
+    }
+
+    /// At most one match per day: the hour (or day) furthest past the threshold
+    pub fn evaluate(
+        &self,
+        favorite: &FavoriteLocation,
+        forecast: &ForecastResponse,
+    ) -> Vec<RuleMatch> {
+        if !self.enabled || !self.applies_to(&favorite.address) {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        for day in self.day.days(forecast) {
+            let hit = match self.hours {
+                None => {
+                    let value = self.metric.day_value(day);
+                    self.comparison
+                        .holds(value, self.threshold)
+                        .then_some((value, None))
+                }
+                Some(window) => day
+                    .hours
+                    .iter()
+                    .filter(|hour| window.contains(&hour.datetime))
+                    .filter_map(|hour| Some((self.metric.hour_value(hour)?, hour)))
+                    .filter(|(value, _)| self.comparison.holds(*value, self.threshold))
+                    .reduce(|worst, next| {
+                        if self.comparison.worst(next.0, worst.0) {
+                            next
+                        } else {
+                            worst
+                        }
+                    })
+                    .map(|(value, hour)| (value, Some(hour.datetime.clone()))),
+            };
+
+            if let Some((value, time)) = hit {
+                matches.push(RuleMatch {
+                    rule: self.clone(),
+                    location: favorite.address.clone(),
+                    name: favorite.name().to_string(),
+                    date: day.datetime.clone(),
+                    time,
+                    value,
+                });
+            }
+        }
+
+        matches
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleMatch {
+    pub rule: Rule,
+    /// The favorite's address, which identifies it
+    pub location: String,
+    /// What the favorite is called in the panel
+    pub name: String,
+    pub date: String,
+    pub time: Option<String>,
+    pub value: f32,
+}
+
+impl RuleMatch {
+    pub fn summary(&self) -> String {
+        format!("{}: {}", self.name, self.rule.metric.label())
+    }
+
+    /// "2.5°C on 2025-01-31 at 07:00 (below 3°C)"
+    pub fn details(&self, units: &Units) -> String {
+        let mut text = format!(
+            "{} on {}",
+            self.rule.metric.format(units, self.value),
+            self.date
+        );
+        if let Some(time) = &self.time {
+            text += &format!(" at {}", time.get(..5).unwrap_or(time));
+        }
+        text += &format!(
+            " ({} {})",
+            self.rule.comparison.label(),
+            self.rule.metric.format(units, self.rule.threshold)
+        );
+        text
+    }
+
+    // the same rule firing for the same place and time is only worth one notification
+    fn key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}",
+            serde_json::to_string(&self.rule).unwrap_or_default(),
+            self.location,
+            self.date,
+            self.time.as_deref().unwrap_or("")
+        )
+    }
+}
+
+/// Where match notifications go. Desktop notifications in the app, `MemorySink` in tests.
+pub trait NotificationSink: Send + Sync {
+    fn notify(&self, summary: &str, body: &str) -> Result<(), anyhow::Error>;
+}
+
+/// Keeps every notification in memory instead of showing it
+#[derive(Default)]
+pub struct MemorySink {
+    sent: Mutex<Vec<(String, String)>>,
+}
+
+impl MemorySink {
+    pub fn sent(&self) -> Vec<(String, String)> {
+        self.sent
+            .lock()
+            .map(|sent| sent.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl NotificationSink for MemorySink {
+    fn notify(&self, summary: &str, body: &str) -> Result<(), anyhow::Error> {
+        self.sent
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{e}"))?
+            .push((summary.to_string(), body.to_string()));
+        Ok(())
+    }
+}
+
+/// Evaluates the rules and notifies each match once
+pub struct RuleEngine {
+    sink: Arc<dyn NotificationSink>,
+    /// Match key -> the location and date it was for
+    notified: HashMap<String, (String, String)>,
+}
+
+impl RuleEngine {
+    pub fn new(sink: Arc<dyn NotificationSink>) -> Self {
+        Self {
+            sink,
+            notified: HashMap::new(),
+        }
+    }
+
+    /// Returns every current match, but only sends notifications for new ones
+    pub fn check(
+        &mut self,
+        rules: &[Rule],
+        favorite: &FavoriteLocation,
+        forecast: &ForecastResponse,
+        units: &Units,
+    ) -> Vec<RuleMatch> {
+        // days the forecast has moved past won't match again, so forget them
+        if let Some(first_day) = forecast.days.first() {
+            self.notified.retain(|_, (notified_location, date)| {
+                *notified_location != favorite.address || *date >= first_day.datetime
+            });
+        }
+
+        let matches: Vec<RuleMatch> = rules
+            .iter()
+            .flat_map(|rule| rule.evaluate(favorite, forecast))
+            .collect();
+
+        for rule_match in &matches {
+            let place = (rule_match.location.clone(), rule_match.date.clone());
+            if self.notified.insert(rule_match.key(), place).is_some() {
+                continue;
+            }
+            if let Err(e) = self
+                .sink
+                .notify(&rule_match.summary(), &rule_match.details(units))
+            {
+                eprintln!("Failed to send notification: {e}");
+            }
+        }
+
+        matches
+    }
+}
+
+/// What the favorite with `address` is called in the panel, or the address itself
+/// once it isn't a favorite any more
+pub fn location_name<'a>(favorites: &'a [FavoriteLocation], address: &'a str) -> &'a str {
+    favorites
+        .iter()
+        .find(|fav| fav.address.eq_ignore_ascii_case(address))
+        .map_or(address, FavoriteLocation::name)
+}
+
+#[derive(Error, Debug)]
+pub enum RulesError {
+    #[error("can't read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("{} is damaged ({reason}); fix it or delete it, it won't be overwritten", path.display())]
+    Corrupt { path: PathBuf, reason: String },
+
+    #[error("can't save {}: {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+
+    #[error("no data directory, set XDG_DATA_HOME, HOME or data_dir")]
+    NoDataDir,
+}
+
+const FILE_NAME: &str = "rules.json";
+
+/// rules.json in the data dir, next to the favorites the rules are about. Until it
+/// exists, the one older versions kept next to config.toml is read instead (only for
+/// the default data dir, a `data_dir` of its own starts without rules).
+pub fn read_rules_from_file(config: &Config) -> Result<Vec<Rule>, RulesError> {
+    let path = config.data_path(FILE_NAME).ok_or(RulesError::NoDataDir)?;
+    let legacy = paths::config_dir().map(|dir| dir.join(FILE_NAME));
+    match legacy {
+        Some(legacy) if config.data_dir.is_none() && !path.exists() && legacy.exists() => {
+            load_rules(&legacy)
+        }
+        _ => load_rules(&path),
+    }
+}
+
+/// Refuses to replace a rules file that doesn't parse, so rules the app couldn't read
+/// aren't lost to the next change made in the panel
+pub fn write_rules_to_file(config: &Config, rules: &[Rule]) -> Result<(), RulesError> {
+    save_rules(
+        &config.data_path(FILE_NAME).ok_or(RulesError::NoDataDir)?,
+        rules,
+    )
+}
+
+fn load_rules(path: &Path) -> Result<Vec<Rule>, RulesError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(RulesError::Read {
+                path: path.to_path_buf(),
+                source,
+            });
+        }
+    };
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    serde_json::from_str(&content).map_err(|e| RulesError::Corrupt {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })
+}
+
+fn save_rules(path: &Path, rules: &[Rule]) -> Result<(), RulesError> {
+    load_rules(path)?;
+
+    let write_error = |source| RulesError::Write {
+        path: path.to_path_buf(),
+        source,
+    };
+    let json = serde_json::to_string_pretty(rules).map_err(|e| write_error(e.into()))?;
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir).map_err(write_error)?;
+    }
+    paths::write_atomically(path, json.as_bytes()).map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{UnitOverrides, UnitSystem};
+    use chrono::Utc;
+
+    fn day(datetime: &str, temp_min: f32, hours: &[(&str, f32)]) -> ForecastDay {
+        ForecastDay {
+            datetime: datetime.to_string(),
+            temp_max: temp_min + 8.0,
+            temp_min,
+            temp: temp_min + 4.0,
+            feels_like: temp_min + 2.0,
+            humidity: 70.0,
+            precip: 0.0,
+            precip_prob: hours.iter().map(|&(_, prob)| prob).fold(0.0, f32::max),
+            snow: 0.0,
+            snow_depth: 0.0,
+            wind_speed: 10.0,
+            wind_dir: 180.0,
+            aqieur: None,
+            severe_risk: None,
+            sunrise: None,
+            sunset: None,
+            daylight: None,
+            daylight_change: None,
+            moon_phase: None,
+            conditions: String::new(),
+            icon: String::new(),
+            hours: hours
+                .iter()
+                .map(|&(datetime, precip_prob)| ForecastHour {
+                    datetime: datetime.to_string(),
+                    temp: temp_min,
+                    feels_like: temp_min,
+                    humidity: 70.0,
+                    precip: 0.0,
+                    precip_prob,
+                    wind_speed: 10.0,
+                    wind_dir: 180.0,
+                    cloud_cover: 50.0,
+                    conditions: String::new(),
+                    icon: String::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn response(days: Vec<ForecastDay>) -> ForecastResponse {
+        ForecastResponse {
+            resolved_address: "Iasi".to_string(),
+            latitude: 47.16,
+            longitude: 27.58,
+            days,
+            alerts: Vec::new(),
+            fetched_at: Utc::now(),
+            stale: false,
+        }
+    }
+
+    fn frost(day: DaySelector) -> Rule {
+        Rule {
+            metric: Metric::TempMin,
+            comparison: Comparison::Below,
+            threshold: 0.0,
+            day,
+            hours: None,
+            location: None,
+            enabled: true,
+        }
+    }
+
+    fn morning_rain() -> Rule {
+        Rule {
+            metric: Metric::PrecipProb,
+            comparison: Comparison::Above,
+            threshold: 70.0,
+            day: DaySelector::AnyDay,
+            hours: Some(HourWindow { from: 7, to: 9 }),
+            location: None,
+            enabled: true,
+        }
+    }
+
+    fn place(address: &str) -> FavoriteLocation {
+        FavoriteLocation::new(address.to_string(), 47.16, 27.58)
+    }
+
+    fn dates(matches: &[RuleMatch]) -> Vec<&str> {
+        matches.iter().map(|m| m.date.as_str()).collect()
+    }
+
+    #[test]
+    fn daily_rule_picks_the_selected_days() {
+        let forecast = response(vec![
+            day("2025-01-30", -2.0, &[]),
+            day("2025-01-31", -3.0, &[]),
+            day("2025-02-01", 1.0, &[]),
+        ]);
+
+        let today = frost(DaySelector::Today).evaluate(&place("Iasi"), &forecast);
+        assert_eq!(dates(&today), ["2025-01-30"]);
+        assert_eq!(today[0].value, -2.0);
+        assert_eq!(today[0].time, None);
+
+        let tomorrow = frost(DaySelector::Tomorrow).evaluate(&place("Iasi"), &forecast);
+        assert_eq!(dates(&tomorrow), ["2025-01-31"]);
+        assert_eq!(tomorrow[0].value, -3.0);
+
+        let any_day = frost(DaySelector::AnyDay).evaluate(&place("Iasi"), &forecast);
+        assert_eq!(dates(&any_day), ["2025-01-30", "2025-01-31"]);
+    }
+
+    #[test]
+    fn tomorrow_without_a_second_day_matches_nothing() {
+        let forecast = response(vec![day("2025-01-30", -2.0, &[])]);
+
+        assert!(
+            frost(DaySelector::Tomorrow)
+                .evaluate(&place("Iasi"), &forecast)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn hour_window_reports_the_worst_hour_inside_it() {
+        let forecast = response(vec![
+            day(
+                "2025-01-30",
+                5.0,
+                &[
+                    ("06:00:00", 95.0),
+                    ("07:00:00", 75.0),
+                    ("08:00:00", 80.0),
+                    ("09:00:00", 99.0),
+                ],
+            ),
+            day("2025-01-31", 5.0, &[("06:00:00", 90.0), ("09:00:00", 90.0)]),
+        ]);
+
+        let matches = morning_rain().evaluate(&place("Iasi"), &forecast);
+
+        assert_eq!(dates(&matches), ["2025-01-30"]);
+        assert_eq!(matches[0].time.as_deref(), Some("08:00:00"));
+        assert_eq!(matches[0].value, 80.0);
+    }
+
+    #[test]
+    fn location_and_enabled_filter_rules() {
+        let forecast = response(vec![day("2025-01-30", -2.0, &[])]);
+        let mut rule = frost(DaySelector::Today);
+
+        rule.location = Some("Home".to_string());
+        assert_eq!(rule.evaluate(&place("home"), &forecast).len(), 1);
+        assert!(rule.evaluate(&place("Office"), &forecast).is_empty());
+
+        rule.enabled = false;
+        assert!(rule.evaluate(&place("home"), &forecast).is_empty());
+    }
+
+    #[test]
+    fn engine_notifies_each_match_once() {
+        let sink = Arc::new(MemorySink::default());
+        let mut engine = RuleEngine::new(sink.clone());
+        let units = Units::new(UnitSystem::Metric, UnitOverrides::default());
+        let rules = [frost(DaySelector::AnyDay)];
+        let forecast = response(vec![
+            day("2025-01-30", -2.0, &[]),
+            day("2025-01-31", 2.0, &[]),
+        ]);
+
+        assert_eq!(
+            engine
+                .check(&rules, &place("Iasi"), &forecast, &units)
+                .len(),
+            1
+        );
+        assert_eq!(
+            engine
+                .check(&rules, &place("Iasi"), &forecast, &units)
+                .len(),
+            1
+        );
+        assert_eq!(sink.sent().len(), 1);
+        assert_eq!(sink.sent()[0].0, "Iasi: Lowest temperature");
+
+        // the same match somewhere else is news
+        engine.check(&rules, &place("Suceava"), &forecast, &units);
+        assert_eq!(sink.sent().len(), 2);
+
+        // so is a new day crossing the threshold
+        let colder = response(vec![
+            day("2025-01-30", -2.0, &[]),
+            day("2025-01-31", -1.0, &[]),
+        ]);
+        engine.check(&rules, &place("Iasi"), &colder, &units);
+        assert_eq!(sink.sent().len(), 3);
+    }
+
+    #[test]
+    fn engine_forgets_days_that_passed() {
+        let mut engine = RuleEngine::new(Arc::new(MemorySink::default()));
+        let units = Units::new(UnitSystem::Metric, UnitOverrides::default());
+        let rules = [frost(DaySelector::AnyDay)];
+
+        let monday = response(vec![
+            day("2025-01-27", -2.0, &[]),
+            day("2025-01-28", -2.0, &[]),
+        ]);
+        engine.check(&rules, &place("Iasi"), &monday, &units);
+        engine.check(&rules, &place("Suceava"), &monday, &units);
+        assert_eq!(engine.notified.len(), 4);
+
+        let tuesday = response(vec![
+            day("2025-01-28", -2.0, &[]),
+            day("2025-01-29", 3.0, &[]),
+        ]);
+        engine.check(&rules, &place("Iasi"), &tuesday, &units);
+
+        // Iasi's Monday is gone, Suceava hasn't been checked since
+        let mut kept: Vec<(String, String)> = engine.notified.values().cloned().collect();
+        kept.sort();
+        assert_eq!(
+            kept,
+            [
+                ("Iasi".to_string(), "2025-01-28".to_string()),
+                ("Suceava".to_string(), "2025-01-27".to_string()),
+                ("Suceava".to_string(), "2025-01-28".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_favorites_are_shown_by_their_new_name() {
+        let forecast = response(vec![day("2025-01-30", -2.0, &[])]);
+        let mut home = place("Iasi, Romania");
+        home.display_name = Some("Home".to_string());
+        let mut rule = frost(DaySelector::Today);
+        rule.location = Some("iasi, romania".to_string());
+        let units = Units::new(UnitSystem::Metric, UnitOverrides::default());
+
+        assert_eq!(
+            rule.describe(&units, std::slice::from_ref(&home)),
+            "Lowest temperature below 0°C today in Home"
+        );
+        // no longer a favorite
+        assert_eq!(
+            rule.describe(&units, &[]),
+            "Lowest temperature below 0°C today in iasi, romania"
+        );
+
+        let sink = Arc::new(MemorySink::default());
+        let mut engine = RuleEngine::new(sink.clone());
+        let matches = engine.check(&[rule], &home, &forecast, &units);
+        assert_eq!(matches[0].location, "Iasi, Romania");
+        assert_eq!(sink.sent()[0].0, "Home: Lowest temperature");
+    }
+
+    #[test]
+    fn rules_are_kept_in_the_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            data_dir: Some(dir.path().join("profile")),
+            ..Config::default()
+        };
+
+        assert!(read_rules_from_file(&config).unwrap().is_empty());
+
+        let rules = [frost(DaySelector::Tomorrow), morning_rain()];
+        write_rules_to_file(&config, &rules).unwrap();
+        assert!(dir.path().join("profile").join(FILE_NAME).exists());
+        assert_eq!(read_rules_from_file(&config).unwrap(), rules);
+
+        // another profile has rules of its own
+        let other = Config {
+            data_dir: Some(dir.path().join("other")),
+            ..Config::default()
+        };
+        assert!(read_rules_from_file(&other).unwrap().is_empty());
+    }
+
+    #[test]
+    fn damaged_rules_file_is_never_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules.json");
+        let damaged = "[{\"metric\": \"temp_min\", \"comparison\": ";
+        fs::write(&path, damaged).unwrap();
+
+        assert!(matches!(load_rules(&path), Err(RulesError::Corrupt { .. })));
+        assert!(matches!(
+            save_rules(&path, &[frost(DaySelector::Today)]),
+            Err(RulesError::Corrupt { .. })
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), damaged);
+    }
+}
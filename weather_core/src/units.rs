@@ -0,0 +1,256 @@
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+
+// Providers always hand back metric values (°C, km/h, mm, cm); everything here is
+// display-side, so switching units never needs a new request.
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    #[default]
+    Metric,
+    Us,
+    Uk,
+    Si,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TempUnit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedUnit {
+    Kmh,
+    Mph,
+    Ms,
+    Knots,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LengthUnit {
+    Mm,
+    Cm,
+    Inch,
+}
+
+macro_rules! from_str_via_serde {
+    ($($ty:ty),*) => {
+        $(
+            impl std::str::FromStr for $ty {
+                type Err = anyhow::Error;
+
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    let deserializer: serde::de::value::StrDeserializer<serde::de::value::Error> =
+                        s.into_deserializer();
+                    Self::deserialize(deserializer).map_err(|e| anyhow::anyhow!("{e}"))
+                }
+            }
+        )*
+    };
+}
+
+from_str_via_serde!(UnitSystem, TempUnit, SpeedUnit, LengthUnit);
+
+impl UnitSystem {
+    pub const ALL: [UnitSystem; 4] = [
+        UnitSystem::Metric,
+        UnitSystem::Us,
+        UnitSystem::Uk,
+        UnitSystem::Si,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            UnitSystem::Metric => "Metric",
+            UnitSystem::Us => "US",
+            UnitSystem::Uk => "UK",
+            UnitSystem::Si => "SI",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<UnitSystem> {
+        UnitSystem::ALL.into_iter().find(|s| s.label() == label)
+    }
+}
+
+/// Per-quantity choices that win over whatever the unit system says
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnitOverrides {
+    pub temperature: Option<TempUnit>,
+    pub wind: Option<SpeedUnit>,
+    pub precip: Option<LengthUnit>,
+    pub snow: Option<LengthUnit>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Units {
+    pub temperature: TempUnit,
+    pub wind: SpeedUnit,
+    pub precip: LengthUnit,
+    pub snow: LengthUnit,
+}
+
+impl Units {
+    pub fn new(system: UnitSystem, overrides: UnitOverrides) -> Units {
+        let (temperature, wind, precip, snow) = match system {
+            UnitSystem::Metric => (
+                TempUnit::Celsius,
+                SpeedUnit::Kmh,
+                LengthUnit::Mm,
+                LengthUnit::Cm,
+            ),
+            UnitSystem::Us => (
+                TempUnit::Fahrenheit,
+                SpeedUnit::Mph,
+                LengthUnit::Inch,
+                LengthUnit::Inch,
+            ),
+            UnitSystem::Uk => (
+                TempUnit::Celsius,
+                SpeedUnit::Mph,
+                LengthUnit::Mm,
+                LengthUnit::Cm,
+            ),
+            UnitSystem::Si => (
+                TempUnit::Kelvin,
+                SpeedUnit::Ms,
+                LengthUnit::Mm,
+                LengthUnit::Cm,
+            ),
+        };
+
+        Units {
+            temperature: overrides.temperature.unwrap_or(temperature),
+            wind: overrides.wind.unwrap_or(wind),
+            precip: overrides.precip.unwrap_or(precip),
+            snow: overrides.snow.unwrap_or(snow),
+        }
+    }
+
+    pub fn temp_value(&self, celsius: f32) -> f32 {
+        match self.temperature {
+            TempUnit::Celsius => celsius,
+            TempUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            TempUnit::Kelvin => celsius + 273.15,
+        }
+    }
+
+    pub fn speed_value(&self, kmh: f32) -> f32 {
+        match self.wind {
+            SpeedUnit::Kmh => kmh,
+            SpeedUnit::Mph => kmh / 1.609_344,
+            SpeedUnit::Ms => kmh / 3.6,
+            SpeedUnit::Knots => kmh / 1.852,
+        }
+    }
+
+    pub fn precip_value(&self, mm: f32) -> f32 {
+        length_value(mm, self.precip)
+    }
+
+    pub fn snow_value(&self, cm: f32) -> f32 {
+        length_value(cm * 10.0, self.snow)
+    }
+
+    /// Inverse of `temp_value`, for numbers the user typed in display units
+    pub fn temp_to_celsius(&self, value: f32) -> f32 {
+        match self.temperature {
+            TempUnit::Celsius => value,
+            TempUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            TempUnit::Kelvin => value - 273.15,
+        }
+    }
+
+    pub fn speed_to_kmh(&self, value: f32) -> f32 {
+        value / self.speed_value(1.0)
+    }
+
+    pub fn precip_to_mm(&self, value: f32) -> f32 {
+        value / self.precip_value(1.0)
+    }
+
+    pub fn snow_to_cm(&self, value: f32) -> f32 {
+        value / self.snow_value(1.0)
+    }
+
+    pub fn temp_symbol(&self) -> &'static str {
+        match self.temperature {
+            TempUnit::Celsius => "°C",
+            TempUnit::Fahrenheit => "°F",
+            TempUnit::Kelvin => "K",
+        }
+    }
+
+    pub fn speed_symbol(&self) -> &'static str {
+        match self.wind {
+            SpeedUnit::Kmh => "km/h",
+            SpeedUnit::Mph => "mph",
+            SpeedUnit::Ms => "m/s",
+            SpeedUnit::Knots => "kn",
+        }
+    }
+
+    pub fn precip_symbol(&self) -> &'static str {
+        length_symbol(self.precip)
+    }
+
+    pub fn snow_symbol(&self) -> &'static str {
+        length_symbol(self.snow)
+    }
+
+    pub fn temp(&self, celsius: f32) -> String {
+        match self.temperature {
+            // "12.5°C" but "285.6 K"
+            TempUnit::Kelvin => format!("{} K", round1(self.temp_value(celsius))),
+            _ => format!("{}{}", round1(self.temp_value(celsius)), self.temp_symbol()),
+        }
+    }
+
+    pub fn speed(&self, kmh: f32) -> String {
+        format!("{} {}", round1(self.speed_value(kmh)), self.speed_symbol())
+    }
+
+    pub fn precip(&self, mm: f32) -> String {
+        format_length(self.precip_value(mm), self.precip)
+    }
+
+    pub fn snow(&self, cm: f32) -> String {
+        format_length(self.snow_value(cm), self.snow)
+    }
+}
+
+fn length_value(mm: f32, unit: LengthUnit) -> f32 {
+    match unit {
+        LengthUnit::Mm => mm,
+        LengthUnit::Cm => mm / 10.0,
+        LengthUnit::Inch => mm / 25.4,
+    }
+}
+
+fn length_symbol(unit: LengthUnit) -> &'static str {
+    match unit {
+        LengthUnit::Mm => "mm",
+        LengthUnit::Cm => "cm",
+        LengthUnit::Inch => "in",
+    }
+}
+
+// inches need two decimals, 0.1 in is already a fair amount of rain
+fn format_length(value: f32, unit: LengthUnit) -> String {
+    let rounded = match unit {
+        LengthUnit::Inch => (value * 100.0).round() / 100.0 + 0.0,
+        _ => round1(value),
+    };
+    format!("{rounded} {}", length_symbol(unit))
+}
+
+// `+ 0.0` turns -0.0 into 0.0 so we never show "-0°C"
+pub fn round1(value: f32) -> f32 {
+    (value * 10.0).round() / 10.0 + 0.0
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Provider-neutral types, every client converts its own JSON into these.
+// Values are always metric: °C, km/h, mm of precipitation, cm of snow.
+#[derive(Debug, Clone)]
+pub struct ForecastResponse {
+    pub resolved_address: String,
+
+    pub latitude: f64,
+    pub longitude: f64,
+
+    pub days: Vec<ForecastDay>,
+    /// Everything the provider sent, including alerts that already ended
+    pub alerts: Vec<WeatherAlert>,
+
+    pub fetched_at: DateTime<Utc>,
+    /// Came from the cache after its TTL because the provider couldn't be reached
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForecastDay {
+    pub datetime: String,
+
+    pub temp_max: f32,
+    pub temp_min: f32,
+    pub temp: f32,
+    pub feels_like: f32,
+
+    pub humidity: f32,
+    pub precip: f32,
+    pub precip_prob: f32,
+
+    pub snow: f32,
+    pub snow_depth: f32,
+
+    pub wind_speed: f32,
+    pub wind_dir: f32,
+    pub aqieur: Option<f32>,
+    /// 0-100 risk of severe convective storms, from providers that forecast it
+    pub severe_risk: Option<f32>,
+
+    /// Local "HH:MM:SS", None during polar day or night
+    pub sunrise: Option<String>,
+    pub sunset: Option<String>,
+    /// Seconds between sunrise and sunset
+    pub daylight: Option<i64>,
+    /// Seconds of daylight gained (or lost, if negative) since the day before
+    pub daylight_change: Option<i64>,
+    /// 0 is a new moon, 0.5 a full moon
+    pub moon_phase: Option<f32>,
+
+    pub conditions: String,
+    pub icon: String,
+    pub hours: Vec<ForecastHour>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForecastHour {
+    pub datetime: String,
+
+    pub temp: f32,
+    pub feels_like: f32,
+
+    pub humidity: f32,
+    pub precip: f32,
+    pub precip_prob: f32,
+
+    pub wind_speed: f32,
+    pub wind_dir: f32,
+    /// Percent of the sky covered
+    pub cloud_cover: f32,
+
+    pub conditions: String,
+    pub icon: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CurrentConditions {
+    pub resolved_address: String,
+
+    pub icon: String,
+    pub temp: f32,
+    pub temp_min: f32,
+    pub temp_max: f32,
+
+    pub alerts: Vec<WeatherAlert>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AlertSeverity {
+    Unknown,
+    Minor,
+    Moderate,
+    Severe,
+    Extreme,
+}
+
+impl AlertSeverity {
+    pub fn label(self) -> &'static str {
+        match self {
+            AlertSeverity::Unknown => "Unknown",
+            AlertSeverity::Minor => "Minor",
+            AlertSeverity::Moderate => "Moderate",
+            AlertSeverity::Severe => "Severe",
+            AlertSeverity::Extreme => "Extreme",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WeatherAlert {
+    pub event: String,
+    pub severity: AlertSeverity,
+
+    pub onset: Option<DateTime<Utc>>,
+    pub ends: Option<DateTime<Utc>>,
+
+    pub description: String,
+    /// Affected area, when the provider names one
+    pub area: Option<String>,
+}
+
+impl WeatherAlert {
+    /// Not over yet. Upcoming alerts count too, a storm warning for tonight matters now.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.ends.is_none_or(|ends| ends > now)
+    }
+}
+
+/// Keeps only alerts that haven't ended, most severe (then soonest) first
+pub fn active_alerts(alerts: &[WeatherAlert], now: DateTime<Utc>) -> Vec<WeatherAlert> {
+    let mut active: Vec<WeatherAlert> = alerts
+        .iter()
+        .filter(|alert| alert.is_active(now))
+        .cloned()
+        .collect();
+    active.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.onset.cmp(&b.onset)));
+    active
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FavoriteLocation {
+    /// The name the provider gave the place when it was favorited
+    pub address: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// Shown instead of `address` when the user renamed it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Groups like "Home" or "Ski trips", a favorite can be in several
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl FavoriteLocation {
+    pub fn new(address: String, lat: f64, lon: f64) -> Self {
+        Self {
+            address,
+            lat,
+            lon,
+            display_name: None,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.address)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn matches(&self, lat: f64, lon: f64) -> bool {
+        const EPSILON: f64 = 0.01;
+        (self.lat - lat).abs() < EPSILON && (self.lon - lon).abs() < EPSILON
+    }
+}